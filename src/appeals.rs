@@ -0,0 +1,192 @@
+/*!
+Appeals against deactivated feedback.
+NOTES:
+  - The author of a deactivated feedback files an appeal with a message,
+    feedback hidden for review is handled by the moderation queue instead.
+  - A feedback has at most one appeal kept. Closing is final, after a
+    reinstatement a new appeal can be filed if the feedback is deactivated
+    again.
+  - Moderators reinstate the feedback or close the appeal, the outcome is
+    recorded in the moderation log. Activating appealed feedback directly
+    reinstates it on the appeal too.
+*/
+use crate::*;
+use events::AppealData;
+
+/// Outcome of reviewing an appeal.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, BorshDeserialize, BorshSerialize,
+)]
+pub enum AppealDecision {
+    /// Activate feedback again
+    Reinstate,
+    /// Keep feedback deactivated
+    Close,
+}
+
+/// Appeal of an author on deactivated feedback, open until decided.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Appeal {
+    pub feedback_id: FeedbackId,
+    pub account_id: AccountId,
+    pub message: String,
+    pub create_at: u64,
+    pub decision: Option<AppealDecision>,
+    pub moderator_id: Option<AccountId>,
+    pub reason: Option<String>,
+    pub resolve_at: Option<u64>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Get appeal on feedback
+    /// @param FeedbackId feedback_id
+    /// @return appeal
+    pub fn get_appeal(&self, feedback_id: FeedbackId) -> Option<Appeal> {
+        self.appeals.get(&feedback_id)
+    }
+
+    /// Get open appeals paging, longest waiting first
+    /// @param Cursor cursor
+    /// @param usize size
+    /// @return page
+    pub fn get_open_appeals(&self, cursor: Option<Cursor>, size: usize) -> Page<Appeal> {
+        self.open_appeals
+            .page(cursor, size, |id| self.appeals.get(&id))
+    }
+
+    /// Appeal deactivation of own feedback
+    /// @param FeedbackId feedback_id
+    /// @param String message
+    /// @return appeal
+    #[payable]
+    pub fn file_appeal(&mut self, feedback_id: FeedbackId, message: String) -> Appeal {
+        let initial_storage = env::storage_usage();
+        let feedback = match self.feedback(feedback_id) {
+            Some(feedback) => feedback,
+            None => panic!("Feedback does not exist"),
+        };
+        let account_id = env::predecessor_account_id();
+        assert!(
+            self.user(feedback.user_id)
+                .is_some_and(|user| user.account_id == account_id),
+            "Only feedback author can appeal this feedback."
+        );
+        assert!(!feedback.activate, "Feedback is active");
+        assert!(
+            !self.moderation_queue.contains(feedback_id),
+            "Feedback is pending review"
+        );
+        if let Some(appeal) = self.appeals.get(&feedback_id) {
+            assert!(
+                appeal.decision == Some(AppealDecision::Reinstate),
+                "Feedback is already appealed"
+            );
+        }
+        assert!(!message.is_empty(), "Appeal message is empty");
+
+        let appeal = Appeal {
+            feedback_id,
+            account_id,
+            message,
+            create_at: env::block_timestamp(),
+            decision: None,
+            moderator_id: None,
+            reason: None,
+            resolve_at: None,
+        };
+        self.write_record(
+            StorageRecord::Appeal(feedback_id),
+            &appeal.account_id,
+            |contract| {
+                contract.appeals.insert(&feedback_id, &appeal);
+                contract.open_appeals.insert(feedback_id, appeal.create_at);
+            },
+        );
+
+        self.charge_storage(&appeal.account_id, initial_storage);
+        Event::AppealFiled(AppealData {
+            account_id: &appeal.account_id,
+            appeal: &appeal,
+        })
+        .emit();
+
+        appeal
+    }
+
+    /// Decide open appeal on feedback
+    /// @param FeedbackId feedback_id
+    /// @param AppealDecision decision
+    /// @param String reason
+    /// @return appeal
+    #[payable]
+    pub fn resolve_appeal(
+        &mut self,
+        feedback_id: FeedbackId,
+        decision: AppealDecision,
+        reason: String,
+    ) -> Appeal {
+        let initial_storage = env::storage_usage();
+        self.assert_role(Role::Moderator);
+        assert!(
+            self.open_appeals.contains(feedback_id),
+            "Appeal is not open"
+        );
+        let moderator_id = env::predecessor_account_id();
+        let appeal = self.decide_appeal(feedback_id, decision, reason.clone(), &moderator_id);
+
+        let action = match decision {
+            AppealDecision::Reinstate => {
+                if let Some(mut feedback) = self.feedback(feedback_id) {
+                    self.set_feedback_activate(&mut feedback, true);
+                }
+                ModerationAction::Reinstate
+            }
+            AppealDecision::Close => ModerationAction::CloseAppeal,
+        };
+        self.log_moderation(
+            &moderator_id,
+            ModerationTarget::Feedback(feedback_id),
+            action,
+            Some(reason),
+        );
+
+        self.charge_storage(&moderator_id, initial_storage);
+
+        appeal
+    }
+}
+
+/// Appeal helpers.
+impl Contract {
+    /// Record decision on the open appeal of feedback and take it off the
+    /// open appeals
+    pub(crate) fn decide_appeal(
+        &mut self,
+        feedback_id: FeedbackId,
+        decision: AppealDecision,
+        reason: String,
+        moderator_id: &AccountId,
+    ) -> Appeal {
+        let mut appeal = self.appeals.get(&feedback_id).unwrap();
+        appeal.decision = Some(decision);
+        appeal.moderator_id = Some(moderator_id.clone());
+        appeal.reason = Some(reason);
+        appeal.resolve_at = Some(env::block_timestamp());
+        self.write_record(
+            StorageRecord::Appeal(feedback_id),
+            moderator_id,
+            |contract| {
+                contract.open_appeals.remove(feedback_id);
+                contract.appeals.insert(&feedback_id, &appeal);
+            },
+        );
+        Event::AppealResolved(AppealData {
+            account_id: moderator_id,
+            appeal: &appeal,
+        })
+        .emit();
+
+        appeal
+    }
+}
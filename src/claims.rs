@@ -0,0 +1,210 @@
+/*!
+Company claims and their verification.
+NOTES:
+  - A company representative claims a company with evidence of the domain and
+    the registry number, company verifiers approve or reject the claim.
+  - An approved claim makes the claimant a company manager, able to edit the
+    profile and respond to reviews.
+  - Approved claims are the only way to manage a company, apart from company
+    verifiers managing the companies they create.
+  - An account keeps at most one pending claim per company, a rejected claim
+    can be submitted again with new evidence.
+  - Decisions are recorded in the moderation log of the company.
+  - Approving a claim also makes the verifier pay for the company row.
+*/
+use crate::*;
+use events::ClaimData;
+
+pub type ClaimId = usize;
+
+/// Outcome of verifying a claim.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, BorshDeserialize, BorshSerialize,
+)]
+pub enum ClaimDecision {
+    /// Add claimant to company managers
+    Approve,
+    Reject,
+}
+
+/// Claim of an account to represent a company, pending until decided.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct CompanyClaim {
+    pub id: ClaimId,
+    pub company_id: CompanyId,
+    pub account_id: AccountId,
+    /// Company domain controlled by the claimant
+    pub domain: String,
+    /// Company number in the business registry
+    pub registry_number: String,
+    pub create_at: u64,
+    pub decision: Option<ClaimDecision>,
+    pub verifier_id: Option<AccountId>,
+    pub reason: Option<String>,
+    pub resolve_at: Option<u64>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Get claim by id
+    /// @param ClaimId id
+    /// @return claim
+    pub fn get_claim(&self, id: ClaimId) -> Option<CompanyClaim> {
+        self.claims.get(&id)
+    }
+
+    /// Get last claim of account on company
+    /// @param CompanyId company_id
+    /// @param AccountId account_id
+    /// @return claim
+    pub fn get_company_claim(
+        &self,
+        company_id: CompanyId,
+        account_id: AccountId,
+    ) -> Option<CompanyClaim> {
+        self.claim_ids
+            .get(&(company_id, account_id))
+            .and_then(|id| self.claims.get(&id))
+    }
+
+    /// Get pending claims paging, longest waiting first
+    /// @param Cursor cursor
+    /// @param usize size
+    /// @return page
+    pub fn get_pending_claims(&self, cursor: Option<Cursor>, size: usize) -> Page<CompanyClaim> {
+        self.pending_claims
+            .page(cursor, size, |id| self.claims.get(&id))
+    }
+
+    /// Claim to represent company
+    /// @param CompanyId company_id
+    /// @param String domain
+    /// @param String registry_number
+    /// @return claim
+    #[payable]
+    pub fn submit_claim(
+        &mut self,
+        company_id: CompanyId,
+        domain: String,
+        registry_number: String,
+    ) -> CompanyClaim {
+        let initial_storage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+        self.assert_not_merged(company_id);
+        match self.company(company_id) {
+            Some(company) => assert!(
+                !company.managers.contains(&account_id),
+                "Already company manager"
+            ),
+            None => panic!("Company does not exist"),
+        }
+        assert!(
+            !domain.is_empty() && !registry_number.is_empty(),
+            "Claim requires domain and registry number"
+        );
+        let key = (company_id, account_id);
+        if let Some(id) = self.claim_ids.get(&key) {
+            assert!(
+                !self.pending_claims.contains(id),
+                "Claim is already pending"
+            );
+        }
+
+        let claim = CompanyClaim {
+            id: self.next_claim_id,
+            company_id,
+            account_id: key.1.clone(),
+            domain,
+            registry_number,
+            create_at: env::block_timestamp(),
+            decision: None,
+            verifier_id: None,
+            reason: None,
+            resolve_at: None,
+        };
+        self.write_record(
+            StorageRecord::Claim(claim.id),
+            &claim.account_id,
+            |contract| {
+                contract.claims.insert(&claim.id, &claim);
+                contract.claim_ids.insert(&key, &claim.id);
+                contract.pending_claims.insert(claim.id, claim.create_at);
+            },
+        );
+        self.next_claim_id += 1;
+
+        self.charge_storage(&claim.account_id, initial_storage);
+        Event::ClaimSubmitted(ClaimData {
+            account_id: &claim.account_id,
+            claim: &claim,
+        })
+        .emit();
+
+        claim
+    }
+
+    /// Approve or reject pending claim
+    /// @param ClaimId id
+    /// @param ClaimDecision decision
+    /// @param String reason
+    /// @return claim
+    #[payable]
+    pub fn resolve_claim(
+        &mut self,
+        id: ClaimId,
+        decision: ClaimDecision,
+        reason: String,
+    ) -> CompanyClaim {
+        let initial_storage = env::storage_usage();
+        self.assert_role(Role::CompanyVerifier);
+        assert!(self.pending_claims.contains(id), "Claim is not pending");
+        let mut claim = self.claims.get(&id).unwrap();
+        // A merged company has no managers of its own, claims on it can only
+        // be rejected
+        if decision == ClaimDecision::Approve {
+            self.assert_not_merged(claim.company_id);
+        }
+        let verifier_id = env::predecessor_account_id();
+        claim.decision = Some(decision);
+        claim.verifier_id = Some(verifier_id.clone());
+        claim.reason = Some(reason.clone());
+        claim.resolve_at = Some(env::block_timestamp());
+        self.write_record(StorageRecord::Claim(id), &verifier_id, |contract| {
+            contract.pending_claims.remove(id);
+            contract.claims.insert(&id, &claim);
+        });
+
+        let action = match decision {
+            ClaimDecision::Approve => {
+                if let Some(mut company) = self.company(claim.company_id) {
+                    if !company.managers.contains(&claim.account_id) {
+                        company.managers.push(claim.account_id.clone());
+                        company.update_at = env::block_timestamp();
+                        self.write_record(
+                            StorageRecord::Company(company.id),
+                            &verifier_id,
+                            |contract| contract.save_company(&company),
+                        );
+                    }
+                }
+                ModerationAction::ApproveClaim
+            }
+            ClaimDecision::Reject => ModerationAction::RejectClaim,
+        };
+        self.log_moderation(
+            &verifier_id,
+            ModerationTarget::Company(claim.company_id),
+            action,
+            Some(reason),
+        );
+
+        self.charge_storage(&verifier_id, initial_storage);
+        Event::ClaimResolved(ClaimData {
+            account_id: &verifier_id,
+            claim: &claim,
+        })
+        .emit();
+
+        claim
+    }
+}
@@ -0,0 +1,164 @@
+/*!
+NEP-297 events for indexers.
+NOTES:
+  - Every event is logged as `EVENT_JSON:` followed by
+    `{"standard":"company_feedback","version":"1.0.0","event":...,"data":...}`.
+  - `account_id` in data is the account that made the change.
+  - Create and update events carry the full entity as stored after the change.
+*/
+use crate::*;
+
+pub const EVENT_STANDARD: &str = "company_feedback";
+pub const EVENT_VERSION: &str = "1.0.0";
+
+#[derive(Serialize, Debug)]
+pub struct FeedbackData<'a> {
+    pub account_id: &'a str,
+    pub feedback: &'a Feedback,
+}
+
+#[derive(Serialize, Debug)]
+pub struct UserData<'a> {
+    pub account_id: &'a str,
+    pub user: &'a User,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CompanyData<'a> {
+    pub account_id: &'a str,
+    pub company: &'a Company,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ActivationData<'a> {
+    pub account_id: &'a str,
+    pub id: usize,
+    pub activate: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct VoteData<'a> {
+    pub account_id: &'a str,
+    pub feedback_id: FeedbackId,
+    pub reaction: Option<Reaction>,
+    pub old_reaction: Option<Reaction>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RatingScaleData<'a> {
+    pub account_id: &'a str,
+    pub rating_scale: RatingScale,
+}
+
+#[derive(Serialize, Debug)]
+pub struct OwnerData<'a> {
+    pub account_id: &'a str,
+    pub old_owner_id: Option<&'a str>,
+    pub new_owner_id: Option<&'a str>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RoleData<'a> {
+    pub account_id: &'a str,
+    pub holder_id: &'a str,
+    pub role: Role,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReportData<'a> {
+    pub account_id: &'a str,
+    pub feedback_id: FeedbackId,
+    pub report_vote: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ResolutionData<'a> {
+    pub account_id: &'a str,
+    pub feedback_id: FeedbackId,
+    pub resolution: &'a ReportResolution,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ThresholdData<'a> {
+    pub account_id: &'a str,
+    pub report_threshold: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AppealData<'a> {
+    pub account_id: &'a str,
+    pub appeal: &'a Appeal,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ClaimData<'a> {
+    pub account_id: &'a str,
+    pub claim: &'a CompanyClaim,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ResponseData<'a> {
+    pub account_id: &'a str,
+    pub response: &'a OfficialResponse,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MergeData<'a> {
+    pub account_id: &'a str,
+    pub source_id: CompanyId,
+    pub target_id: CompanyId,
+    pub feedback_count: usize,
+}
+
+/// Contract events, named in snake case in the log.
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum Event<'a> {
+    FeedbackCreated(FeedbackData<'a>),
+    FeedbackUpdated(FeedbackData<'a>),
+    FeedbackActivationChanged(ActivationData<'a>),
+    FeedbackVoted(VoteData<'a>),
+    FeedbackUnvoted(VoteData<'a>),
+    UserCreated(UserData<'a>),
+    UserUpdated(UserData<'a>),
+    UserActivationChanged(ActivationData<'a>),
+    CompanyCreated(CompanyData<'a>),
+    CompanyUpdated(CompanyData<'a>),
+    CompanyActivationChanged(ActivationData<'a>),
+    RatingScaleUpdated(RatingScaleData<'a>),
+    OwnerProposed(OwnerData<'a>),
+    OwnershipTransferred(OwnerData<'a>),
+    RoleGranted(RoleData<'a>),
+    RoleRevoked(RoleData<'a>),
+    FeedbackQueued(ReportData<'a>),
+    ReportResolved(ResolutionData<'a>),
+    ReportThresholdUpdated(ThresholdData<'a>),
+    AppealFiled(AppealData<'a>),
+    AppealResolved(AppealData<'a>),
+    ClaimSubmitted(ClaimData<'a>),
+    ClaimResolved(ClaimData<'a>),
+    OfficialResponsePosted(ResponseData<'a>),
+    OfficialResponseUpdated(ResponseData<'a>),
+    CompaniesMerged(MergeData<'a>),
+}
+
+#[derive(Serialize)]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+impl Event<'_> {
+    // Log event in NEP-297 format
+    pub fn emit(&self) {
+        let log = EventLog {
+            standard: EVENT_STANDARD,
+            version: EVENT_VERSION,
+            event: self,
+        };
+        let json = serde_json::to_string(&log).expect("Event is not serializable");
+        env::log(format!("EVENT_JSON:{}", json).as_bytes());
+    }
+}
@@ -4,32 +4,157 @@ NOTES:
   -
 */
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::UnorderedMap;
+use near_sdk::collections::{LookupMap, UnorderedMap, Vector};
+use near_sdk::json_types::{I128, U64};
 use near_sdk::{env, near_bindgen, AccountId, BorshStorageKey};
 use serde::{Deserialize, Serialize};
 use std::clone::Clone;
+use std::cmp::Ordering;
 use std::panic;
 
+mod appeals;
+mod claims;
+mod events;
+mod merge;
+mod migration;
+mod moderation;
+mod ownership;
+mod queue;
+mod responses;
+mod roles;
+mod storage;
+
+pub use appeals::{Appeal, AppealDecision};
+pub use claims::{ClaimDecision, ClaimId, CompanyClaim};
+pub use events::Event;
+use events::{ActivationData, CompanyData, FeedbackData, RatingScaleData, UserData, VoteData};
+pub use migration::{Migration, MigrationStage};
+pub use moderation::{
+    ModerationAction, ModerationEntry, ModerationTarget, ReportDecision, ReportResolution,
+};
+pub use queue::IdQueue;
+pub use responses::OfficialResponse;
+pub use roles::Role;
+pub use storage::{RecordPayment, StorageAccount, StorageRecord};
+
 near_sdk::setup_alloc!();
 
 pub type FeedbackId = usize;
 pub type UserId = usize;
 pub type CompanyId = usize;
 
+/// Upper bound of a configurable rating scale
+pub const MAX_RATING: usize = 100;
+
+/// Hot score precision kept in integer sort keys
+pub const HOT_SCORE_SCALE: f64 = 10_000_000.0;
+
+/// Rows a page reads past its size before it is cut short, so runs of
+/// inactive rows don't make a page read the whole listing
+pub const MAX_SKIPPED_ROWS: usize = 50;
+
+/// Newest feedbacks of a listing read by a sorted or filtered query
+pub const MAX_QUERY_SCAN: usize = 200;
+
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Contract {
-    /// contract owner
-    pub owner_id: String,
+    /// contract owner, none once renounced
+    pub owner_id: Option<AccountId>,
+
+    /// Account proposed as next owner until it accepts
+    pub pending_owner_id: Option<AccountId>,
+
+    /// Roles granted by account
+    pub roles: LookupMap<AccountId, Vec<Role>>,
 
     /// All feedbacks
-    pub feedbacks: UnorderedMap<FeedbackId, Feedback>,
+    pub feedbacks: UnorderedMap<FeedbackId, VersionedFeedback>,
 
     /// All user
-    pub users: UnorderedMap<UserId, User>,
+    pub users: UnorderedMap<UserId, VersionedUser>,
+
+    /// User id by bound account
+    pub user_ids_by_account: LookupMap<AccountId, UserId>,
+
+    /// Vote reaction by feedback and account
+    pub votes: LookupMap<(FeedbackId, AccountId), Reaction>,
+
+    /// Feedback ids by company in creation order
+    pub feedbacks_by_company: LookupMap<CompanyId, Vector<FeedbackId>>,
+
+    /// Feedback ids by author user in creation order
+    pub feedbacks_by_user: LookupMap<UserId, Vector<FeedbackId>>,
+
+    /// Reply ids by parent feedback in creation order
+    pub feedbacks_by_parent: LookupMap<FeedbackId, Vector<FeedbackId>>,
+
+    /// Active feedback count by listing
+    pub feedback_counts: LookupMap<FeedbackListing, usize>,
+
+    /// Storage deposit by account
+    pub storage_accounts: LookupMap<AccountId, StorageAccount>,
+
+    /// Payer of records other accounts may rewrite or free
+    pub record_payments: LookupMap<StorageRecord, RecordPayment>,
+
+    /// Bytes charged to other accounts than the caller during this call
+    #[borsh_skip]
+    pub accounted_storage: i64,
 
     /// All companies
-    pub companies: UnorderedMap<CompanyId, Company>,
+    pub companies: UnorderedMap<CompanyId, VersionedCompany>,
+
+    /// Active company count
+    pub active_company_count: usize,
+
+    /// Allowed rating range of top-level reviews
+    pub rating_scale: RatingScale,
+
+    /// Review count indexed by rating for each company
+    pub rating_histograms: LookupMap<CompanyId, Vec<usize>>,
+
+    /// Reports hiding feedback for review
+    pub report_threshold: usize,
+
+    /// Time hidden feedback was queued for review
+    pub moderation_queue: IdQueue,
+
+    /// Last decision on reported feedback
+    pub report_resolutions: LookupMap<FeedbackId, ReportResolution>,
+
+    /// Append-only log of moderation actions
+    pub moderation_log: Vector<ModerationEntry>,
+
+    /// Moderation log entry ids by target in log order
+    pub moderation_log_by_target: LookupMap<ModerationTarget, Vector<usize>>,
+
+    /// Last appeal by feedback
+    pub appeals: LookupMap<FeedbackId, Appeal>,
+
+    /// Time open appeals were filed
+    pub open_appeals: IdQueue,
+
+    /// All company claims
+    pub claims: LookupMap<ClaimId, CompanyClaim>,
+
+    /// Last claim id by company and claimant
+    pub claim_ids: LookupMap<(CompanyId, AccountId), ClaimId>,
+
+    /// Time pending claims were submitted
+    pub pending_claims: IdQueue,
+
+    /// Official company response by top-level review
+    pub official_responses: LookupMap<FeedbackId, OfficialResponse>,
+
+    /// Company id by normalized name
+    pub company_ids_by_name: LookupMap<String, CompanyId>,
+
+    /// Target company id by merged company
+    pub company_redirects: LookupMap<CompanyId, CompanyId>,
+
+    /// Version 0 rows left to convert, none once migrated
+    pub migration: Option<Migration>,
 
     // Next id for feedback
     pub next_feedback_id: FeedbackId,
@@ -39,16 +164,34 @@ pub struct Contract {
 
     // Next id for company
     pub next_company_id: CompanyId,
+
+    // Next id for company claim
+    pub next_claim_id: ClaimId,
+}
+
+/// Reaction of an account on a feedback.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, BorshDeserialize, BorshSerialize,
+)]
+pub enum Reaction {
+    /// Up vote on parent feedback
+    Like,
+    /// Down vote on parent feedback
+    Dislike,
+    /// Report parent feedback to moderators
+    Report,
+    /// No reaction, used by top-level reviews and plain replies
+    None,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, BorshDeserialize, BorshSerialize)]
 pub struct Feedback {
     pub id: FeedbackId,
-    pub parent_id: FeedbackId,
+    pub parent_id: Option<FeedbackId>,
     pub user_id: UserId,
     pub company_id: CompanyId,
     pub content: String,
-    pub reaction: usize,
+    pub reaction: Reaction,
     pub rating: usize,
     pub up_vote: usize,
     pub down_vote: usize,
@@ -58,20 +201,16 @@ pub struct Feedback {
     pub activate: bool,
 }
 impl Feedback {
-    // reaction = 0 // like
-    // reaction = 1 // dislike
-    // reaction = 2 // ban
-
     // Return mock feedback
     pub fn mock() -> Feedback {
         Feedback {
             id: 0,
-            parent_id: 0,
+            parent_id: None,
             user_id: 0,
             company_id: 0,
             content: "Mock feedback".to_string(),
-            reaction: 0,
-            rating: 0,
+            reaction: Reaction::None,
+            rating: 5,
             up_vote: 0,
             down_vote: 0,
             report_vote: 0,
@@ -80,12 +219,41 @@ impl Feedback {
             activate: true,
         }
     }
+
+    // Count vote by reaction
+    pub fn add_vote(&mut self, reaction: Reaction) {
+        match reaction {
+            Reaction::Like => self.up_vote += 1,
+            Reaction::Dislike => self.down_vote += 1,
+            Reaction::Report => self.report_vote += 1,
+            Reaction::None => {}
+        }
+    }
+
+    // Discount vote by reaction
+    pub fn remove_vote(&mut self, reaction: Reaction) {
+        match reaction {
+            Reaction::Like => self.up_vote -= 1,
+            Reaction::Dislike => self.down_vote -= 1,
+            Reaction::Report => self.report_vote -= 1,
+            Reaction::None => {}
+        }
+    }
+
+    // Score net votes on a log scale plus creation time, so ten times the
+    // votes is worth 12.5 hours of freshness and scores never decay
+    pub fn hot_score(&self) -> i128 {
+        let net_votes = self.up_vote as f64 - self.down_vote as f64;
+        let order = net_votes.abs().max(1.0).log10() * net_votes.signum();
+        let seconds = (self.create_at / 1_000_000_000) as f64;
+        ((order + seconds / 45_000.0) * HOT_SCORE_SCALE) as i128
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, BorshDeserialize, BorshSerialize)]
 pub struct User {
     pub id: UserId,
-    pub address: String,
+    pub account_id: AccountId,
     pub name: String,
     pub title: String,
     pub create_at: u64,
@@ -97,7 +265,7 @@ impl User {
     pub fn mock() -> Self {
         Self {
             id: 0,
-            address: env::predecessor_account_id(),
+            account_id: env::predecessor_account_id(),
             name: "Mock User".to_string(),
             title: "Mock Title".to_string(),
             create_at: 0,
@@ -116,6 +284,7 @@ pub struct Company {
     pub company_type: String,
     pub size: String,
     pub address: String,
+    pub managers: Vec<AccountId>,
     pub create_at: u64,
     pub update_at: u64,
     pub activate: bool,
@@ -131,6 +300,7 @@ impl Company {
             company_type: "Mock Company Type".to_string(),
             size: "Mock Company Size".to_string(),
             address: "Mock Company Address".to_string(),
+            managers: vec![],
             create_at: 0,
             update_at: 0,
             activate: true,
@@ -138,9 +308,324 @@ impl Company {
     }
 }
 
+/// Stored feedback layouts. A changed Feedback keeps its previous layout
+/// as a new variant, so old rows still read and convert to the current one.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum VersionedFeedback {
+    V1(Feedback),
+}
+impl From<VersionedFeedback> for Feedback {
+    fn from(feedback: VersionedFeedback) -> Self {
+        match feedback {
+            VersionedFeedback::V1(feedback) => feedback,
+        }
+    }
+}
+
+/// Stored user layouts, see VersionedFeedback.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum VersionedUser {
+    V1(User),
+}
+impl From<VersionedUser> for User {
+    fn from(user: VersionedUser) -> Self {
+        match user {
+            VersionedUser::V1(user) => user,
+        }
+    }
+}
+
+/// Stored company layouts, see VersionedFeedback.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum VersionedCompany {
+    V1(Company),
+}
+impl From<VersionedCompany> for Company {
+    fn from(company: VersionedCompany) -> Self {
+        match company {
+            VersionedCompany::V1(company) => company,
+        }
+    }
+}
+
+/// Feedback with the official response of its company.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FeedbackView {
+    pub id: FeedbackId,
+    pub parent_id: Option<FeedbackId>,
+    pub user_id: UserId,
+    pub company_id: CompanyId,
+    pub content: String,
+    pub reaction: Reaction,
+    pub rating: usize,
+    pub up_vote: usize,
+    pub down_vote: usize,
+    pub report_vote: usize,
+    pub create_at: u64,
+    pub update_at: u64,
+    pub activate: bool,
+    /// Pinned response of the company, top-level reviews only
+    pub official_response: Option<OfficialResponse>,
+}
+
+/// Company with its computed rating summary.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CompanyView {
+    pub id: CompanyId,
+    pub name: String,
+    pub average_rating: f64,
+    pub rating_count: usize,
+    /// Review count for each rating from the scale min to max
+    pub rating_histogram: Vec<usize>,
+    pub company_type: String,
+    pub size: String,
+    pub address: String,
+    pub managers: Vec<AccountId>,
+    pub create_at: u64,
+    pub update_at: u64,
+    pub activate: bool,
+}
+
+/// Allowed range of review ratings, inclusive.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, BorshDeserialize, BorshSerialize,
+)]
+pub struct RatingScale {
+    pub min: usize,
+    pub max: usize,
+}
+impl Default for RatingScale {
+    fn default() -> Self {
+        Self { min: 1, max: 5 }
+    }
+}
+impl RatingScale {
+    // Check rating is within scale
+    pub fn contains(&self, rating: usize) -> bool {
+        self.min <= rating && rating <= self.max
+    }
+}
+
+/// Feedback listings with a maintained count of active feedbacks.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeedbackListing {
+    All,
+    Company(CompanyId),
+    CompanyRoot(CompanyId),
+    User(UserId),
+    Parent(FeedbackId),
+}
+impl FeedbackListing {
+    // Check feedback belongs to listing
+    pub fn contains(&self, feedback: &Feedback) -> bool {
+        match *self {
+            FeedbackListing::All => true,
+            FeedbackListing::Company(company_id) => feedback.company_id == company_id,
+            FeedbackListing::CompanyRoot(company_id) => {
+                feedback.company_id == company_id && feedback.parent_id.is_none()
+            }
+            FeedbackListing::User(user_id) => feedback.user_id == user_id,
+            FeedbackListing::Parent(parent_id) => feedback.parent_id == Some(parent_id),
+        }
+    }
+}
+
+/// Position of the last item seen in a listing.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Cursor {
+    /// Sort key of the listing, a string in JSON to keep precision
+    pub key: I128,
+    pub id: usize,
+}
+
+/// Feedback listing order, ties broken by id in the same direction.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FeedbackSort {
+    #[default]
+    Newest,
+    Oldest,
+    HighestRating,
+    MostUpvoted,
+    Hot,
+}
+impl FeedbackSort {
+    // Return sort key of feedback
+    pub fn key(&self, feedback: &Feedback) -> i128 {
+        match self {
+            FeedbackSort::Newest | FeedbackSort::Oldest => feedback.create_at as i128,
+            FeedbackSort::HighestRating => feedback.rating as i128,
+            FeedbackSort::MostUpvoted => feedback.up_vote as i128,
+            FeedbackSort::Hot => feedback.hot_score(),
+        }
+    }
+
+    // Return cursor positioned at feedback
+    pub fn cursor(&self, feedback: &Feedback) -> Cursor {
+        Cursor {
+            key: self.key(feedback).into(),
+            id: feedback.id,
+        }
+    }
+
+    // Compare feedbacks in listing order
+    pub fn compare(&self, a: &Feedback, b: &Feedback) -> Ordering {
+        let a = (self.key(a), a.id);
+        let b = (self.key(b), b.id);
+        match self {
+            FeedbackSort::Oldest => a.cmp(&b),
+            _ => b.cmp(&a),
+        }
+    }
+
+    // Check feedback comes after cursor in listing order
+    pub fn is_after(&self, feedback: &Feedback, cursor: &Cursor) -> bool {
+        let position = (self.key(feedback), feedback.id);
+        let cursor = (cursor.key.0, cursor.id);
+        match self {
+            FeedbackSort::Oldest => position > cursor,
+            _ => position < cursor,
+        }
+    }
+}
+
+/// Sort and filter options of feedback listings, all filters optional.
+/// Queries only read the newest MAX_QUERY_SCAN feedbacks of the listing,
+/// older ones are left out of the items and the total and the page is
+/// marked truncated.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct FeedbackQuery {
+    pub sort: FeedbackSort,
+    pub min_rating: Option<usize>,
+    pub max_rating: Option<usize>,
+    /// Earliest create_at included
+    pub from: Option<U64>,
+    /// Latest create_at excluded
+    pub to: Option<U64>,
+    /// Only feedbacks with this reaction
+    pub reaction: Option<Reaction>,
+}
+impl FeedbackQuery {
+    // Check feedback passes all filters
+    pub fn matches(&self, feedback: &Feedback) -> bool {
+        self.min_rating.is_none_or(|min| feedback.rating >= min)
+            && self.max_rating.is_none_or(|max| feedback.rating <= max)
+            && self.from.is_none_or(|from| feedback.create_at >= from.0)
+            && self.to.is_none_or(|to| feedback.create_at < to.0)
+            && self
+                .reaction
+                .is_none_or(|reaction| feedback.reaction == reaction)
+    }
+}
+
+/// One page of a listing with the total count and the cursor of the next page.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+    /// Listing was read only in part, total counts the rows read
+    pub truncated: bool,
+    pub next_cursor: Option<Cursor>,
+}
+
+/// Editable fields of a feedback, merged onto the stored feedback.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FeedbackPatch {
+    pub content: Option<String>,
+    pub rating: Option<usize>,
+}
+impl FeedbackPatch {
+    // Merge patch onto feedback
+    pub fn apply(self, feedback: &mut Feedback) {
+        if let Some(content) = self.content {
+            feedback.content = content;
+        }
+        if let Some(rating) = self.rating {
+            feedback.rating = rating;
+        }
+    }
+}
+
+/// Editable fields of a user, merged onto the stored user.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UserPatch {
+    pub name: Option<String>,
+    pub title: Option<String>,
+}
+impl UserPatch {
+    // Merge patch onto user
+    pub fn apply(self, user: &mut User) {
+        if let Some(name) = self.name {
+            user.name = name;
+        }
+        if let Some(title) = self.title {
+            user.title = title;
+        }
+    }
+}
+
+/// Editable fields of a company, merged onto the stored company.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CompanyPatch {
+    pub name: Option<String>,
+    pub company_type: Option<String>,
+    pub size: Option<String>,
+    pub address: Option<String>,
+}
+impl CompanyPatch {
+    // Merge patch onto company
+    pub fn apply(self, company: &mut Company) {
+        if let Some(name) = self.name {
+            company.name = name;
+        }
+        if let Some(company_type) = self.company_type {
+            company.company_type = company_type;
+        }
+        if let Some(size) = self.size {
+            company.size = size;
+        }
+        if let Some(address) = self.address {
+            company.address = address;
+        }
+    }
+}
+
 /// Helper structure to for keys of the persistent collections.
 #[derive(BorshStorageKey, BorshSerialize)]
 pub enum StorageKey {
+    // Prefixes of version 0 rows, never reused by the current layout
+    FeedbacksV0,
+    UsersV0,
+    CompaniesV0,
+    UserIdsByAccount,
+    Votes,
+    RatingHistograms,
+    FeedbacksByCompany,
+    FeedbacksByUser,
+    FeedbacksByParent,
+    CompanyFeedbacks { company_id: CompanyId },
+    UserFeedbacks { user_id: UserId },
+    ParentFeedbacks { parent_id: FeedbackId },
+    FeedbackCounts,
+    StorageAccounts,
+    Roles,
+    ModerationQueue,
+    ReportResolutions,
+    ModerationLog,
+    ModerationLogByTarget,
+    TargetModerationLog { target: ModerationTarget },
+    Appeals,
+    OpenAppeals,
+    Claims,
+    ClaimIds,
+    PendingClaims,
+    OfficialResponses,
+    CompanyIdsByName,
+    CompanyRedirects,
+    RecordPayments,
+    ModerationQueueOrder,
+    OpenAppealsOrder,
+    PendingClaimsOrder,
     Feedbacks,
     Users,
     Companies,
@@ -156,96 +641,219 @@ impl Contract {
             "Owner's account ID is invalid."
         );
         assert!(!env::state_exists(), "Already initialized");
-        Self {
-            owner_id: owner_id,
+        let contract = Self {
+            owner_id: Some(owner_id),
+            pending_owner_id: None,
+            roles: LookupMap::new(StorageKey::Roles),
             feedbacks: UnorderedMap::new(StorageKey::Feedbacks),
             users: UnorderedMap::new(StorageKey::Users),
+            user_ids_by_account: LookupMap::new(StorageKey::UserIdsByAccount),
+            votes: LookupMap::new(StorageKey::Votes),
+            feedbacks_by_company: LookupMap::new(StorageKey::FeedbacksByCompany),
+            feedbacks_by_user: LookupMap::new(StorageKey::FeedbacksByUser),
+            feedbacks_by_parent: LookupMap::new(StorageKey::FeedbacksByParent),
+            feedback_counts: LookupMap::new(StorageKey::FeedbackCounts),
+            storage_accounts: LookupMap::new(StorageKey::StorageAccounts),
+            record_payments: LookupMap::new(StorageKey::RecordPayments),
+            accounted_storage: 0,
             companies: UnorderedMap::new(StorageKey::Companies),
+            active_company_count: 0,
+            rating_scale: RatingScale::default(),
+            rating_histograms: LookupMap::new(StorageKey::RatingHistograms),
+            report_threshold: moderation::DEFAULT_REPORT_THRESHOLD,
+            moderation_queue: IdQueue::new(
+                StorageKey::ModerationQueue,
+                StorageKey::ModerationQueueOrder,
+            ),
+            report_resolutions: LookupMap::new(StorageKey::ReportResolutions),
+            moderation_log: Vector::new(StorageKey::ModerationLog),
+            moderation_log_by_target: LookupMap::new(StorageKey::ModerationLogByTarget),
+            appeals: LookupMap::new(StorageKey::Appeals),
+            open_appeals: IdQueue::new(StorageKey::OpenAppeals, StorageKey::OpenAppealsOrder),
+            claims: LookupMap::new(StorageKey::Claims),
+            claim_ids: LookupMap::new(StorageKey::ClaimIds),
+            pending_claims: IdQueue::new(StorageKey::PendingClaims, StorageKey::PendingClaimsOrder),
+            official_responses: LookupMap::new(StorageKey::OfficialResponses),
+            company_ids_by_name: LookupMap::new(StorageKey::CompanyIdsByName),
+            company_redirects: LookupMap::new(StorageKey::CompanyRedirects),
+            migration: None,
             next_feedback_id: 0,
             next_user_id: 0,
             next_company_id: 0,
-        }
+            next_claim_id: 0,
+        };
+        migration::write_state_version();
+
+        contract
     }
 
     pub fn only_contract_owner(&mut self) {
-        assert_eq!(
-            env::predecessor_account_id(),
-            self.owner_id,
+        assert!(
+            self.is_contract_owner(&env::predecessor_account_id()),
             "Only contract owner can call this method."
         );
     }
 
-    /// Update activate feedback
+    /// Update activate feedback, recording the change in the moderation log
     /// @param FeedbackId id
     /// @param bool activate
+    /// @param String reason
     /// @return feedback
-    pub fn update_active_feedback(&mut self, id: FeedbackId, activate: bool) -> Feedback {
-        self.only_contract_owner();
-        match self.feedbacks.get(&id).as_mut() {
-            Some(feedback) => {
-                feedback.activate = activate;
-                feedback.update_at = env::block_timestamp().into();
-                self.feedbacks.insert(&id, feedback);
-                feedback.clone()
-            }
+    #[payable]
+    pub fn update_active_feedback(
+        &mut self,
+        id: FeedbackId,
+        activate: bool,
+        reason: Option<String>,
+    ) -> Feedback {
+        let initial_storage = env::storage_usage();
+        self.assert_role(Role::Moderator);
+        let mut feedback = match self.feedback(id) {
+            Some(feedback) => feedback,
             None => panic!("Feedback does not exist"),
+        };
+        self.set_feedback_activate(&mut feedback, activate);
+
+        let caller = env::predecessor_account_id();
+        // Activated feedback is no longer pending review, it is restored
+        if activate && self.moderation_queue.contains(id) {
+            self.record_resolution(
+                &feedback,
+                ReportDecision::Restore,
+                reason.clone().unwrap_or_default(),
+                caller.clone(),
+            );
+        }
+        // Activated feedback has nothing left to appeal
+        if activate && self.open_appeals.contains(id) {
+            self.decide_appeal(
+                id,
+                AppealDecision::Reinstate,
+                reason.clone().unwrap_or_default(),
+                &caller,
+            );
         }
+        self.log_moderation(
+            &caller,
+            ModerationTarget::Feedback(id),
+            ModerationAction::activation(activate),
+            reason,
+        );
+        self.charge_storage(&caller, initial_storage);
+
+        feedback
     }
 
-    /// Update activate user
+    /// Update activate user, recording the change in the moderation log
     /// @param UserId id
     /// @param bool activate
+    /// @param String reason
     /// @return user
-    pub fn update_active_user(&mut self, id: UserId, activate: bool) -> User {
-        self.only_contract_owner();
-        match self.users.get(&id).as_mut() {
-            Some(user) => {
-                user.activate = activate;
-                user.update_at = env::block_timestamp().into();
-                self.users.insert(&id, user);
-                user.clone()
-            }
+    #[payable]
+    pub fn update_active_user(
+        &mut self,
+        id: UserId,
+        activate: bool,
+        reason: Option<String>,
+    ) -> User {
+        let initial_storage = env::storage_usage();
+        self.assert_role(Role::Moderator);
+        let mut user = match self.user(id) {
+            Some(user) => user,
             None => panic!("User does not exist"),
-        }
+        };
+        user.activate = activate;
+        user.update_at = env::block_timestamp();
+        self.save_user(&user);
+
+        let caller = env::predecessor_account_id();
+        self.log_moderation(
+            &caller,
+            ModerationTarget::User(id),
+            ModerationAction::activation(activate),
+            reason,
+        );
+        self.charge_storage(&caller, initial_storage);
+        Event::UserActivationChanged(ActivationData {
+            account_id: &caller,
+            id,
+            activate,
+        })
+        .emit();
+
+        user
     }
 
-    /// Update activate company
+    /// Update activate company, recording the change in the moderation log
     /// @param CompanyId id
     /// @param bool activate
+    /// @param String reason
     /// @return company
-    pub fn update_active_company(&mut self, id: CompanyId, activate: bool) -> Company {
-        self.only_contract_owner();
-        match self.companies.get(&id).as_mut() {
-            Some(company) => {
-                company.activate = activate;
-                company.update_at = env::block_timestamp().into();
-                self.companies.insert(&id, company);
-                company.clone()
-            }
+    #[payable]
+    pub fn update_active_company(
+        &mut self,
+        id: CompanyId,
+        activate: bool,
+        reason: Option<String>,
+    ) -> Company {
+        let initial_storage = env::storage_usage();
+        self.assert_role(Role::CompanyVerifier);
+        self.assert_not_merged(id);
+        let mut company = match self.company(id) {
+            Some(company) => company,
             None => panic!("Company does not exist"),
-        }
+        };
+        self.set_company_activate(&mut company, activate);
+
+        let caller = env::predecessor_account_id();
+        self.log_moderation(
+            &caller,
+            ModerationTarget::Company(id),
+            ModerationAction::activation(activate),
+            reason,
+        );
+        self.charge_storage(&caller, initial_storage);
+        Event::CompanyActivationChanged(ActivationData {
+            account_id: &caller,
+            id,
+            activate,
+        })
+        .emit();
+
+        company
     }
 
-    /// Get feedbacks paging
-    /// @param usize page
+    /// Get feedbacks paging, newest first unless query sorts otherwise
+    /// @param Cursor cursor
     /// @param usize size
-    /// @return feedbacks
-    pub fn get_feedbacks(&self, page: usize, size: usize) -> Vec<Feedback> {
-        let mut feedbacks = vec![];
-        for (_, feedback) in self.feedbacks.iter() {
-            if feedback.activate {
-                feedbacks.push(feedback);
+    /// @param FeedbackQuery query
+    /// @return page
+    pub fn get_feedbacks(
+        &self,
+        cursor: Option<Cursor>,
+        size: usize,
+        query: Option<FeedbackQuery>,
+    ) -> Page<FeedbackView> {
+        match query.filter(|query| *query != FeedbackQuery::default()) {
+            Some(query) => self.query_feedbacks(
+                (0..self.next_feedback_id).rev(),
+                FeedbackListing::All,
+                &query,
+                cursor,
+                size,
+            ),
+            None => {
+                let start = cursor.map_or(self.next_feedback_id, |cursor| cursor.id);
+                self.page_feedbacks((0..start).rev(), FeedbackListing::All, size)
             }
         }
-        feedbacks.sort_by(|a, b| b.create_at.cmp(&a.create_at));
-        feedbacks.into_iter().skip(page * size).take(size).collect()
     }
 
     /// Get activate feedback by id
     /// @param FeedbackId id
     /// @return feedback
     pub fn get_feedback(&self, id: FeedbackId) -> Feedback {
-        match self.feedbacks.get(&id) {
+        match self.feedback(id) {
             Some(feedback) => feedback,
             None => panic!("Feedback does not exist"),
         }
@@ -253,272 +861,995 @@ impl Contract {
 
     /// Get activate feedbacks by user_id paging
     /// @param UserId user_id
-    /// @param usize page
+    /// @param Cursor cursor
     /// @param usize size
-    /// @return feedbacks
+    /// @return page
     pub fn get_feedbacks_by_user_id_paging(
         &self,
         user_id: UserId,
-        page: usize,
+        cursor: Option<Cursor>,
         size: usize,
-    ) -> Vec<Feedback> {
-        let mut feedbacks = vec![];
-        for (_, feedback) in self.feedbacks.iter() {
-            if feedback.user_id == user_id && feedback.activate {
-                feedbacks.push(feedback);
-            }
-        }
-        feedbacks.sort_by(|a, b| b.create_at.cmp(&a.create_at));
-        feedbacks.into_iter().skip(page * size).take(size).collect()
+    ) -> Page<FeedbackView> {
+        self.page_feedback_index(
+            self.feedbacks_by_user.get(&user_id),
+            FeedbackListing::User(user_id),
+            cursor,
+            size,
+        )
     }
 
     /// Get activate feedbacks by parent_id paging
     /// @param FeedbackId parent_id
-    /// @param usize page
+    /// @param Cursor cursor
     /// @param usize size
-    /// @return feedbacks
+    /// @return page
     pub fn get_feedbacks_by_parent_id_paging(
         &self,
         parent_id: FeedbackId,
-        page: usize,
+        cursor: Option<Cursor>,
         size: usize,
-    ) -> Vec<Feedback> {
-        let mut feedbacks = vec![];
-        for (_, feedback) in self.feedbacks.iter() {
-            if feedback.parent_id == parent_id && feedback.activate {
-                feedbacks.push(feedback);
-            }
-        }
-        feedbacks.sort_by(|a, b| b.create_at.cmp(&a.create_at));
-        feedbacks.into_iter().skip(page * size).take(size).collect()
+    ) -> Page<FeedbackView> {
+        self.page_feedback_index(
+            self.feedbacks_by_parent.get(&parent_id),
+            FeedbackListing::Parent(parent_id),
+            cursor,
+            size,
+        )
     }
 
     /// Get activate feedbacks by company_id paging
     /// @param CompanyId company_id
-    /// @param usize page
+    /// @param Cursor cursor
     /// @param usize size
-    /// @return feedbacks
+    /// @param FeedbackQuery query
+    /// @return page
     pub fn get_feedbacks_by_company_id_paging(
         &self,
         company_id: CompanyId,
-        page: usize,
+        cursor: Option<Cursor>,
         size: usize,
-    ) -> Vec<Feedback> {
-        let mut feedbacks = vec![];
-        for (_, feedback) in self.feedbacks.iter() {
-            if feedback.company_id == company_id && feedback.activate {
-                feedbacks.push(feedback);
-            }
+        query: Option<FeedbackQuery>,
+    ) -> Page<FeedbackView> {
+        let company_id = self.resolve_company_id(company_id);
+        let ids = self.feedbacks_by_company.get(&company_id);
+        let listing = FeedbackListing::Company(company_id);
+        match query.filter(|query| *query != FeedbackQuery::default()) {
+            Some(query) => self.query_feedbacks(newest_ids(ids), listing, &query, cursor, size),
+            None => self.page_feedback_index(ids, listing, cursor, size),
+        }
+    }
+
+    /// Get activate top-level feedbacks by company_id paging
+    /// @param CompanyId company_id
+    /// @param Cursor cursor
+    /// @param usize size
+    /// @param FeedbackQuery query
+    /// @return page
+    pub fn get_root_feedbacks_by_company(
+        &self,
+        company_id: CompanyId,
+        cursor: Option<Cursor>,
+        size: usize,
+        query: Option<FeedbackQuery>,
+    ) -> Page<FeedbackView> {
+        let company_id = self.resolve_company_id(company_id);
+        let ids = self.feedbacks_by_company.get(&company_id);
+        let listing = FeedbackListing::CompanyRoot(company_id);
+        match query.filter(|query| *query != FeedbackQuery::default()) {
+            Some(query) => self.query_feedbacks(newest_ids(ids), listing, &query, cursor, size),
+            None => self.page_feedback_index(ids, listing, cursor, size),
         }
-        feedbacks.sort_by(|a, b| b.create_at.cmp(&a.create_at));
-        feedbacks.into_iter().skip(page * size).take(size).collect()
     }
 
-    /// Get activate companies paging
-    /// @param usize page
+    /// Get activate companies paging, newest first
+    /// @param Cursor cursor
     /// @param usize size
-    /// @return companies
-    pub fn get_companies_paging(&self, page: usize, size: usize) -> Vec<Company> {
+    /// @return page
+    pub fn get_companies_paging(&self, cursor: Option<Cursor>, size: usize) -> Page<CompanyView> {
+        let start = cursor.map_or(self.next_company_id, |cursor| cursor.id);
+        let id_cursor = |id: CompanyId| Cursor {
+            key: (id as i128).into(),
+            id,
+        };
         let mut companies = vec![];
-        for (_, company) in self.companies.iter() {
-            if company.activate {
+        let mut next_cursor_id = None;
+        for (read, id) in (0..start).rev().enumerate() {
+            // Cut the page short at the last company read
+            if read == size + MAX_SKIPPED_ROWS {
+                next_cursor_id = Some(id + 1);
+                break;
+            }
+            if let Some(company) = self.company(id).filter(|company| company.activate) {
                 companies.push(company);
+                if companies.len() > size {
+                    break;
+                }
             }
         }
-        companies.sort_by(|a, b| b.update_at.cmp(&a.update_at));
-        companies.into_iter().skip(page * size).take(size).collect()
+        let next_cursor = match next_cursor_id {
+            Some(id) => Some(id_cursor(id)),
+            None => next_cursor(&mut companies, size, |company| id_cursor(company.id)),
+        };
+        Page {
+            items: companies
+                .into_iter()
+                .map(|company| self.company_view(company))
+                .collect(),
+            total: self.active_company_count,
+            truncated: false,
+            next_cursor,
+        }
     }
 
     /// Get activate company by id
     /// @param CompanyId id
     /// @return company
-    pub fn get_company(&self, id: CompanyId) -> Company {
-        match self.companies.get(&id) {
-            Some(company) => company,
+    pub fn get_company(&self, id: CompanyId) -> CompanyView {
+        match self.company(self.resolve_company_id(id)) {
+            Some(company) => self.company_view(company),
             None => panic!("Company does not exist"),
         }
     }
 
+    /// Get rating scale of top-level reviews
+    /// @return rating_scale
+    pub fn get_rating_scale(&self) -> RatingScale {
+        self.rating_scale
+    }
+
+    /// Set rating scale of top-level reviews, only before the first feedback
+    /// so stored ratings and company averages share one scale
+    /// @param usize min
+    /// @param usize max
+    /// @return rating_scale
+    pub fn set_rating_scale(&mut self, min: usize, max: usize) -> RatingScale {
+        self.only_contract_owner();
+        assert!(
+            1 <= min && min <= max && max <= MAX_RATING,
+            "Rating scale must be within 1 and {}",
+            MAX_RATING
+        );
+        assert!(
+            self.next_feedback_id == 0,
+            "Rating scale can't change once feedback exists"
+        );
+        self.rating_scale = RatingScale { min, max };
+        Event::RatingScaleUpdated(RatingScaleData {
+            account_id: &env::predecessor_account_id(),
+            rating_scale: self.rating_scale,
+        })
+        .emit();
+        self.rating_scale
+    }
+
     /// Create new feedback
     /// @param Feedback feedback
     /// @return feedback
+    #[payable]
     pub fn create_feedback(&mut self, feedback: Feedback) -> Feedback {
+        let initial_storage = env::storage_usage();
+        assert!(
+            feedback.reaction == Reaction::None,
+            "Use vote to react to feedback"
+        );
+
+        // Author is the user bound to the caller account
+        let user_id = match self.user_ids_by_account.get(&env::predecessor_account_id()) {
+            Some(user_id) => user_id,
+            None => panic!("User does not exist"),
+        };
+        match self.user(user_id) {
+            Some(user) => assert!(user.activate, "User is not active"),
+            None => panic!("User does not exist"),
+        }
+        match self.company(feedback.company_id) {
+            Some(company) => assert!(company.activate, "Company is not active"),
+            None => panic!("Company does not exist"),
+        }
+        if let Some(parent_id) = feedback.parent_id {
+            match self.feedback(parent_id) {
+                Some(parent) => {
+                    assert!(parent.activate, "Parent feedback is not active");
+                    assert!(
+                        parent.company_id == feedback.company_id,
+                        "Reply must be on the company of its parent"
+                    );
+                }
+                None => panic!("Parent feedback does not exist"),
+            }
+        }
+        self.assert_rating(&feedback);
+
         let id = self.next_feedback_id;
 
         let mut feedback = feedback;
         feedback.id = id;
+        feedback.user_id = user_id;
         feedback.up_vote = 0;
         feedback.down_vote = 0;
         feedback.report_vote = 0;
-        feedback.create_at = env::block_timestamp().into();
-        feedback.update_at = env::block_timestamp().into();
+        feedback.create_at = env::block_timestamp();
+        feedback.update_at = env::block_timestamp();
         feedback.activate = true;
 
         // Create new feedback
-        self.feedbacks.insert(&id, &feedback);
+        self.save_feedback(&feedback);
+        self.index_feedback(&feedback);
+        self.count_feedback(&feedback, true);
         self.next_feedback_id += 1;
 
-        // Update parent feedback up_vote, down_vote, ban_vote by reaction
-        match self.feedbacks.get(&feedback.parent_id).as_mut() {
-            Some(parent_feedback) => {
-                if parent_feedback.id != feedback.id {
-                    if feedback.reaction == 0 {
-                        parent_feedback.up_vote += 1;
-                    }
-                    if feedback.reaction == 1 {
-                        parent_feedback.down_vote += 1;
-                    }
-                    if feedback.reaction == 2 {
-                        parent_feedback.report_vote += 1;
-                    }
-
-                    self.feedbacks.insert(&parent_feedback.id, &parent_feedback);
-                }
-            }
-            None => {
-                env::log(format!("Didn't find feedback by parent_id.").as_bytes());
-            }
-        };
-
         // Update company rating
-        match self.companies.get(&feedback.company_id).as_mut() {
-            Some(company) => {
-                company.rating = company.rating + feedback.rating;
-                company.rating_count = company.rating_count + 1;
-                self.companies.insert(&feedback.company_id, &company);
-            }
-            None => {
-                env::log(format!("Didn't find company by id.").as_bytes());
-            }
-        };
+        self.sync_rating(None, &feedback);
+        self.charge_storage(&env::predecessor_account_id(), initial_storage);
+        Event::FeedbackCreated(FeedbackData {
+            account_id: &env::predecessor_account_id(),
+            feedback: &feedback,
+        })
+        .emit();
 
         feedback
     }
 
     /// Update feedback by id
     /// @param FeedbackId id
-    /// @param Feedback feedback
+    /// @param FeedbackPatch patch
     /// @return feedback
-    pub fn update_feedback(&mut self, id: FeedbackId, feedback: Feedback) -> Feedback {
-        let mut feedback = feedback;
-
-        // Update feedback
-        match self.feedbacks.get(&id).as_mut() {
-            Some(_) => {
-                feedback.update_at = env::block_timestamp().into();
-                self.feedbacks.insert(&id, &feedback);
-            }
+    #[payable]
+    pub fn update_feedback(&mut self, id: FeedbackId, patch: FeedbackPatch) -> Feedback {
+        let initial_storage = env::storage_usage();
+        let mut feedback = match self.feedback(id) {
+            Some(feedback) => feedback,
             None => panic!("Feedback does not exist"),
         };
+        self.assert_feedback_author(&feedback);
+
+        let old_feedback = feedback.clone();
+        patch.apply(&mut feedback);
+        // Reviews migrated unrated stay so until a rating is set
+        if feedback.rating != old_feedback.rating {
+            self.assert_rating(&feedback);
+        }
+        feedback.update_at = env::block_timestamp();
+        let row_storage = env::storage_usage();
+        self.save_feedback(&feedback);
+        if let Some(author) = self.user(feedback.user_id) {
+            self.charge_row(&author.account_id, row_storage);
+        }
 
         // Update company rating
-        match self.companies.get(&feedback.company_id).as_mut() {
-            Some(company) => {
-                let feedback_find_by_id = self.feedbacks.get(&id).unwrap();
-                company.rating = company.rating - feedback_find_by_id.rating + feedback.rating;
-                self.companies.insert(&company.id, &company);
-            }
-            None => {}
+        self.sync_rating(Some(&old_feedback), &feedback);
+        self.charge_storage(&env::predecessor_account_id(), initial_storage);
+        Event::FeedbackUpdated(FeedbackData {
+            account_id: &env::predecessor_account_id(),
+            feedback: &feedback,
+        })
+        .emit();
+
+        feedback
+    }
+
+    /// Vote on feedback, replacing any previous vote of the caller
+    /// @param FeedbackId feedback_id
+    /// @param Reaction reaction
+    /// @return feedback
+    #[payable]
+    pub fn vote(&mut self, feedback_id: FeedbackId, reaction: Reaction) -> Feedback {
+        let initial_storage = env::storage_usage();
+        assert!(reaction != Reaction::None, "Vote requires a reaction");
+        let mut feedback = match self.feedback(feedback_id) {
+            Some(feedback) => feedback,
+            None => panic!("Feedback does not exist"),
+        };
+        assert!(feedback.activate, "Feedback is not active");
+
+        let key = (feedback_id, env::predecessor_account_id());
+        let old_reaction = self.votes.insert(&key, &reaction);
+        if let Some(old_reaction) = old_reaction {
+            feedback.remove_vote(old_reaction);
+        }
+        feedback.add_vote(reaction);
+        self.save_feedback(&feedback);
+        if reaction == Reaction::Report {
+            self.check_reports(&mut feedback);
+        }
+        self.charge_storage(&key.1, initial_storage);
+        Event::FeedbackVoted(VoteData {
+            account_id: &key.1,
+            feedback_id,
+            reaction: Some(reaction),
+            old_reaction,
+        })
+        .emit();
+
+        feedback
+    }
+
+    /// Remove vote of the caller from feedback
+    /// @param FeedbackId feedback_id
+    /// @return feedback
+    #[payable]
+    pub fn unvote(&mut self, feedback_id: FeedbackId) -> Feedback {
+        let initial_storage = env::storage_usage();
+        let mut feedback = match self.feedback(feedback_id) {
+            Some(feedback) => feedback,
+            None => panic!("Feedback does not exist"),
         };
 
+        let key = (feedback_id, env::predecessor_account_id());
+        let old_reaction = match self.votes.remove(&key) {
+            Some(old_reaction) => old_reaction,
+            None => panic!("Vote does not exist"),
+        };
+        feedback.remove_vote(old_reaction);
+        self.save_feedback(&feedback);
+        self.charge_storage(&key.1, initial_storage);
+        Event::FeedbackUnvoted(VoteData {
+            account_id: &key.1,
+            feedback_id,
+            reaction: None,
+            old_reaction: Some(old_reaction),
+        })
+        .emit();
+
         feedback
     }
 
-    /// Create new user
+    /// Get vote of account on feedback
+    /// @param FeedbackId feedback_id
+    /// @param AccountId account_id
+    /// @return reaction
+    pub fn get_vote(&self, feedback_id: FeedbackId, account_id: AccountId) -> Option<Reaction> {
+        self.votes.get(&(feedback_id, account_id))
+    }
+
+    /// Create new user bound to the caller account
     /// @param User user
     /// @return user
+    #[payable]
     pub fn create_user(&mut self, user: User) -> User {
+        let initial_storage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+        assert!(
+            self.user_ids_by_account.get(&account_id).is_none(),
+            "Account already has a user"
+        );
+
         let id = self.next_user_id;
 
         let mut user = user;
 
         user.id = id;
-        user.create_at = env::block_timestamp().into();
-        user.update_at = env::block_timestamp().into();
+        user.account_id = account_id;
+        user.create_at = env::block_timestamp();
+        user.update_at = env::block_timestamp();
         user.activate = true;
 
-        self.users.insert(&id, &user);
+        self.save_user(&user);
+        self.user_ids_by_account.insert(&user.account_id, &id);
         self.next_user_id += 1;
+        self.charge_storage(&user.account_id, initial_storage);
+        Event::UserCreated(UserData {
+            account_id: &user.account_id,
+            user: &user,
+        })
+        .emit();
 
         user
     }
 
     /// Update user by id
     /// @param UserId id
-    /// @param User user
+    /// @param UserPatch patch
     /// @return user
-    pub fn update_user(&mut self, id: UserId, user: User) -> User {
-        match self.users.get(&id) {
-            Some(_) => {
-                let mut user = user;
-                user.update_at = env::block_timestamp().into();
-                self.users.insert(&id, &user);
-                user
+    #[payable]
+    pub fn update_user(&mut self, id: UserId, patch: UserPatch) -> User {
+        let initial_storage = env::storage_usage();
+        let user = match self.user(id).as_mut() {
+            Some(user) => {
+                self.assert_user_account(user);
+                patch.apply(user);
+                user.update_at = env::block_timestamp();
+                let row_storage = env::storage_usage();
+                self.save_user(user);
+                self.charge_row(&user.account_id, row_storage);
+                user.clone()
             }
             None => panic!("User does not exist"),
-        }
+        };
+        self.charge_storage(&env::predecessor_account_id(), initial_storage);
+        Event::UserUpdated(UserData {
+            account_id: &env::predecessor_account_id(),
+            user: &user,
+        })
+        .emit();
+
+        user
+    }
+
+    /// Get user bound to account
+    /// @param AccountId account_id
+    /// @return user
+    pub fn get_user_by_account(&self, account_id: AccountId) -> Option<User> {
+        self.user_ids_by_account
+            .get(&account_id)
+            .and_then(|id| self.user(id))
     }
 
-    /// Create new company
+    /// Create new company, managed by the caller only if it is a company
+    /// verifier, anyone else has to claim it
     /// @param Company company
     /// @return company
+    #[payable]
     pub fn create_company(&mut self, company: Company) -> Company {
+        let initial_storage = env::storage_usage();
         let id = self.next_company_id;
         let mut company = company;
 
         company.id = id;
-        company.create_at = env::block_timestamp().into();
-        company.update_at = env::block_timestamp().into();
+        company.rating = 0;
+        company.rating_count = 0;
+        company.managers = if self.has_role(env::predecessor_account_id(), Role::CompanyVerifier) {
+            vec![env::predecessor_account_id()]
+        } else {
+            vec![]
+        };
+        company.create_at = env::block_timestamp();
+        company.update_at = env::block_timestamp();
         company.activate = false;
 
-        self.companies.insert(&id, &company);
+        self.write_record(
+            StorageRecord::Company(id),
+            &env::predecessor_account_id(),
+            |contract| {
+                contract.index_company_name(&company);
+                contract.save_company(&company);
+            },
+        );
         self.next_company_id += 1;
+        self.charge_storage(&env::predecessor_account_id(), initial_storage);
+        Event::CompanyCreated(CompanyData {
+            account_id: &env::predecessor_account_id(),
+            company: &company,
+        })
+        .emit();
 
         company
     }
 
     /// Update company by id
     /// @param CompanyId id
-    /// @param Company company
+    /// @param CompanyPatch patch
     /// @return company
-    pub fn update_company(&mut self, id: CompanyId, company: Company) -> Company {
-        match self.companies.get(&id) {
-            Some(_) => {
-                let mut company = company;
-                company.update_at = env::block_timestamp().into();
-                self.companies.insert(&id, &company);
-                company
-            }
+    #[payable]
+    pub fn update_company(&mut self, id: CompanyId, patch: CompanyPatch) -> Company {
+        let initial_storage = env::storage_usage();
+        let mut company = match self.company(id) {
+            Some(company) => company,
             None => panic!("Company does not exist"),
+        };
+        self.assert_company_manager(&company);
+        self.assert_not_merged(id);
+        let old_name = company.name.clone();
+        patch.apply(&mut company);
+        company.update_at = env::block_timestamp();
+        self.write_record(
+            StorageRecord::Company(id),
+            &env::predecessor_account_id(),
+            |contract| {
+                contract.reindex_company_name(&old_name, &company);
+                contract.save_company(&company);
+            },
+        );
+        self.charge_storage(&env::predecessor_account_id(), initial_storage);
+        Event::CompanyUpdated(CompanyData {
+            account_id: &env::predecessor_account_id(),
+            company: &company,
+        })
+        .emit();
+
+        company
+    }
+}
+
+/// Versioned entity helpers.
+impl Contract {
+    /// Read feedback in the current layout
+    pub(crate) fn feedback(&self, id: FeedbackId) -> Option<Feedback> {
+        self.feedbacks.get(&id).map(Feedback::from)
+    }
+
+    /// Write feedback in the current layout
+    pub(crate) fn save_feedback(&mut self, feedback: &Feedback) {
+        self.assert_migrated();
+        self.feedbacks
+            .insert(&feedback.id, &VersionedFeedback::V1(feedback.clone()));
+    }
+
+    /// Read user in the current layout
+    pub(crate) fn user(&self, id: UserId) -> Option<User> {
+        self.users.get(&id).map(User::from)
+    }
+
+    /// Write user in the current layout
+    pub(crate) fn save_user(&mut self, user: &User) {
+        self.assert_migrated();
+        self.users
+            .insert(&user.id, &VersionedUser::V1(user.clone()));
+    }
+
+    /// Read company in the current layout
+    pub(crate) fn company(&self, id: CompanyId) -> Option<Company> {
+        self.companies.get(&id).map(Company::from)
+    }
+
+    /// Write company in the current layout
+    pub(crate) fn save_company(&mut self, company: &Company) {
+        self.assert_migrated();
+        self.companies
+            .insert(&company.id, &VersionedCompany::V1(company.clone()));
+    }
+}
+
+/// Company rating helpers.
+impl Contract {
+    /// Top-level reviews must be rated within scale, replies carry no rating
+    fn assert_rating(&self, feedback: &Feedback) {
+        if feedback.parent_id.is_some() {
+            assert_eq!(feedback.rating, 0, "Reply cannot carry a rating");
+        } else {
+            assert!(
+                self.rating_scale.contains(feedback.rating),
+                "Rating must be between {} and {}",
+                self.rating_scale.min,
+                self.rating_scale.max
+            );
+        }
+    }
+
+    /// Only active top-level reviews count towards their company rating,
+    /// reviews migrated unrated carry rating 0 and are left out
+    fn counted_rating(feedback: &Feedback) -> Option<usize> {
+        if feedback.parent_id.is_none() && feedback.activate && feedback.rating != 0 {
+            Some(feedback.rating)
+        } else {
+            None
+        }
+    }
+
+    /// Move company rating aggregates from the old to the new state of a feedback,
+    /// covering rating edits, company changes and activation changes
+    fn sync_rating(&mut self, old_feedback: Option<&Feedback>, feedback: &Feedback) {
+        if let Some(old_feedback) = old_feedback {
+            if let Some(rating) = Self::counted_rating(old_feedback) {
+                self.remove_rating(old_feedback.company_id, rating);
+            }
+        }
+        if let Some(rating) = Self::counted_rating(feedback) {
+            self.add_rating(feedback.company_id, rating);
+        }
+    }
+
+    /// Activate or deactivate feedback, keeping ratings and counts in sync
+    fn set_feedback_activate(&mut self, feedback: &mut Feedback, activate: bool) {
+        let old_feedback = feedback.clone();
+        feedback.activate = activate;
+        feedback.update_at = env::block_timestamp();
+        self.save_feedback(feedback);
+        self.sync_rating(Some(&old_feedback), feedback);
+        if old_feedback.activate != activate {
+            self.count_feedback(feedback, activate);
+        }
+        Event::FeedbackActivationChanged(ActivationData {
+            account_id: &env::predecessor_account_id(),
+            id: feedback.id,
+            activate,
+        })
+        .emit();
+    }
+
+    /// Activate or deactivate company, keeping the active count in sync
+    fn set_company_activate(&mut self, company: &mut Company, activate: bool) {
+        if company.activate != activate {
+            if activate {
+                self.active_company_count += 1;
+            } else {
+                self.active_company_count -= 1;
+            }
+        }
+        company.activate = activate;
+        company.update_at = env::block_timestamp();
+        self.save_company(company);
+    }
+
+    /// Count rating into company sum, count and histogram
+    fn add_rating(&mut self, company_id: CompanyId, rating: usize) {
+        if let Some(mut company) = self.company(company_id) {
+            company.rating += rating;
+            company.rating_count += 1;
+            self.save_company(&company);
+
+            let initial_storage = env::storage_usage();
+            let mut histogram = self.rating_histograms.get(&company_id).unwrap_or_default();
+            if histogram.len() <= rating {
+                histogram.resize(rating + 1, 0);
+            }
+            histogram[rating] += 1;
+            self.rating_histograms.insert(&company_id, &histogram);
+            self.fund_by_contract(initial_storage);
+        }
+    }
+
+    /// Discount rating from company sum, count and histogram
+    fn remove_rating(&mut self, company_id: CompanyId, rating: usize) {
+        if let Some(mut company) = self.company(company_id) {
+            company.rating -= rating;
+            company.rating_count -= 1;
+            self.save_company(&company);
+
+            let initial_storage = env::storage_usage();
+            let mut histogram = self.rating_histograms.get(&company_id).unwrap_or_default();
+            histogram[rating] -= 1;
+            self.rating_histograms.insert(&company_id, &histogram);
+            self.fund_by_contract(initial_storage);
+        }
+    }
+
+    fn feedback_view(&self, feedback: Feedback) -> FeedbackView {
+        FeedbackView {
+            official_response: self.official_responses.get(&feedback.id),
+            id: feedback.id,
+            parent_id: feedback.parent_id,
+            user_id: feedback.user_id,
+            company_id: feedback.company_id,
+            content: feedback.content,
+            reaction: feedback.reaction,
+            rating: feedback.rating,
+            up_vote: feedback.up_vote,
+            down_vote: feedback.down_vote,
+            report_vote: feedback.report_vote,
+            create_at: feedback.create_at,
+            update_at: feedback.update_at,
+            activate: feedback.activate,
+        }
+    }
+
+    fn company_view(&self, company: Company) -> CompanyView {
+        let histogram = self.rating_histograms.get(&company.id).unwrap_or_default();
+        let average_rating = if company.rating_count == 0 {
+            0.0
+        } else {
+            company.rating as f64 / company.rating_count as f64
+        };
+        CompanyView {
+            id: company.id,
+            name: company.name,
+            average_rating,
+            rating_count: company.rating_count,
+            rating_histogram: (self.rating_scale.min..=self.rating_scale.max)
+                .map(|rating| histogram.get(rating).copied().unwrap_or(0))
+                .collect(),
+            company_type: company.company_type,
+            size: company.size,
+            address: company.address,
+            managers: company.managers,
+            create_at: company.create_at,
+            update_at: company.update_at,
+            activate: company.activate,
+        }
+    }
+}
+
+/// Feedback index helpers.
+impl Contract {
+    /// Add feedback to the company, user and parent indexes
+    fn index_feedback(&mut self, feedback: &Feedback) {
+        let initial_storage = env::storage_usage();
+        push_feedback_id(
+            &mut self.feedbacks_by_company,
+            &feedback.company_id,
+            StorageKey::CompanyFeedbacks {
+                company_id: feedback.company_id,
+            },
+            feedback.id,
+        );
+        push_feedback_id(
+            &mut self.feedbacks_by_user,
+            &feedback.user_id,
+            StorageKey::UserFeedbacks {
+                user_id: feedback.user_id,
+            },
+            feedback.id,
+        );
+        if let Some(parent_id) = feedback.parent_id {
+            push_feedback_id(
+                &mut self.feedbacks_by_parent,
+                &parent_id,
+                StorageKey::ParentFeedbacks { parent_id },
+                feedback.id,
+            );
+        }
+        self.fund_by_contract(initial_storage);
+    }
+
+    /// Count feedback in or out of the active counts of its listings
+    fn count_feedback(&mut self, feedback: &Feedback, active: bool) {
+        let initial_storage = env::storage_usage();
+        let mut listings = vec![
+            FeedbackListing::All,
+            FeedbackListing::Company(feedback.company_id),
+            FeedbackListing::User(feedback.user_id),
+        ];
+        match feedback.parent_id {
+            Some(parent_id) => listings.push(FeedbackListing::Parent(parent_id)),
+            None => listings.push(FeedbackListing::CompanyRoot(feedback.company_id)),
+        }
+        for listing in listings {
+            let count = self.feedback_counts.get(&listing).unwrap_or(0);
+            let count = if active { count + 1 } else { count - 1 };
+            self.feedback_counts.insert(&listing, &count);
+        }
+        self.fund_by_contract(initial_storage);
+    }
+
+    /// Page through active feedbacks of an index after cursor, newest first
+    fn page_feedback_index(
+        &self,
+        ids: Option<Vector<FeedbackId>>,
+        listing: FeedbackListing,
+        cursor: Option<Cursor>,
+        size: usize,
+    ) -> Page<FeedbackView> {
+        let ids = match ids {
+            Some(ids) => ids,
+            None => {
+                return Page {
+                    items: vec![],
+                    total: 0,
+                    truncated: false,
+                    next_cursor: None,
+                }
+            }
+        };
+        // Index ids are ascending, so start below the first id not before cursor
+        let start = match cursor {
+            Some(cursor) => {
+                let (mut low, mut high) = (0, ids.len());
+                while low < high {
+                    let middle = (low + high) / 2;
+                    if ids.get(middle).unwrap() < cursor.id {
+                        low = middle + 1;
+                    } else {
+                        high = middle;
+                    }
+                }
+                low
+            }
+            None => ids.len(),
+        };
+        let feedback_ids = (0..start).rev().filter_map(|index| ids.get(index));
+        self.page_feedbacks(feedback_ids, listing, size)
+    }
+
+    /// Take one page of active feedbacks of a listing from ids, newest first.
+    /// Reads at most size + MAX_SKIPPED_ROWS rows, a short page then
+    /// ends at the last row read and the next page continues from there.
+    fn page_feedbacks<I: Iterator<Item = FeedbackId>>(
+        &self,
+        ids: I,
+        listing: FeedbackListing,
+        size: usize,
+    ) -> Page<FeedbackView> {
+        let total = self.feedback_counts.get(&listing).unwrap_or(0);
+        let mut feedbacks = vec![];
+        let mut last_read = None;
+        let mut next_cursor_read = None;
+        for (read, id) in ids.enumerate() {
+            if read == size + MAX_SKIPPED_ROWS {
+                next_cursor_read = Some(last_read);
+                break;
+            }
+            if let Some(feedback) = self.feedback(id) {
+                last_read = Some(FeedbackSort::Newest.cursor(&feedback));
+                if feedback.activate && listing.contains(&feedback) {
+                    feedbacks.push(feedback);
+                    if feedbacks.len() > size {
+                        break;
+                    }
+                }
+            }
+        }
+        let next_cursor = match next_cursor_read {
+            // Cut the page short at the last row read
+            Some(last_read) => last_read,
+            None => next_cursor(&mut feedbacks, size, |feedback| {
+                FeedbackSort::Newest.cursor(feedback)
+            }),
+        };
+        Page {
+            items: feedbacks
+                .into_iter()
+                .map(|feedback| self.feedback_view(feedback))
+                .collect(),
+            total,
+            truncated: false,
+            next_cursor,
+        }
+    }
+
+    /// Take one page of active feedbacks of a listing matching query, in
+    /// query order. Reads the first MAX_QUERY_SCAN ids to sort and count
+    /// matches, so ids have to come newest first.
+    fn query_feedbacks<I: Iterator<Item = FeedbackId>>(
+        &self,
+        ids: I,
+        listing: FeedbackListing,
+        query: &FeedbackQuery,
+        cursor: Option<Cursor>,
+        size: usize,
+    ) -> Page<FeedbackView> {
+        let mut ids = ids.peekable();
+        let mut feedbacks: Vec<Feedback> = ids
+            .by_ref()
+            .take(MAX_QUERY_SCAN)
+            .filter_map(|id| self.feedback(id))
+            .filter(|feedback| {
+                feedback.activate && listing.contains(feedback) && query.matches(feedback)
+            })
+            .collect();
+        let total = feedbacks.len();
+        feedbacks.sort_by(|a, b| query.sort.compare(a, b));
+
+        let mut feedbacks: Vec<Feedback> = feedbacks
+            .into_iter()
+            .filter(|feedback| cursor.is_none_or(|cursor| query.sort.is_after(feedback, &cursor)))
+            .take(size + 1)
+            .collect();
+        let next_cursor = next_cursor(&mut feedbacks, size, |feedback| query.sort.cursor(feedback));
+        Page {
+            items: feedbacks
+                .into_iter()
+                .map(|feedback| self.feedback_view(feedback))
+                .collect(),
+            total,
+            truncated: ids.peek().is_some(),
+            next_cursor,
         }
     }
 }
 
+fn push_feedback_id<K: BorshSerialize>(
+    index: &mut LookupMap<K, Vector<FeedbackId>>,
+    key: &K,
+    prefix: StorageKey,
+    id: FeedbackId,
+) {
+    let mut ids = index.get(key).unwrap_or_else(|| Vector::new(prefix));
+    ids.push(&id);
+    index.insert(key, &ids);
+}
+
+/// Iterate ids of an index newest first without loading the whole index
+fn newest_ids(ids: Option<Vector<FeedbackId>>) -> impl Iterator<Item = FeedbackId> {
+    ids.into_iter()
+        .flat_map(|ids| (0..ids.len()).rev().filter_map(move |index| ids.get(index)))
+}
+
+/// Trim items fetched one past the page size, returning the cursor of the
+/// last kept item when more items follow.
+fn next_cursor<T, F: Fn(&T) -> Cursor>(
+    items: &mut Vec<T>,
+    size: usize,
+    cursor: F,
+) -> Option<Cursor> {
+    if items.len() > size {
+        items.truncate(size);
+        items.last().map(cursor)
+    } else {
+        None
+    }
+}
+
+/// Access control helpers.
+impl Contract {
+    fn is_contract_owner(&self, account_id: &str) -> bool {
+        self.owner_id.as_deref() == Some(account_id)
+    }
+
+    /// Only the author of the feedback or contract owner may modify it
+    fn assert_feedback_author(&self, feedback: &Feedback) {
+        let caller = env::predecessor_account_id();
+        let is_author = self
+            .user(feedback.user_id)
+            .is_some_and(|user| user.account_id == caller);
+        assert!(
+            is_author || self.is_contract_owner(&caller),
+            "Only feedback author can update this feedback."
+        );
+    }
+
+    /// Only the account bound to the user or contract owner may modify it
+    fn assert_user_account(&self, user: &User) {
+        let caller = env::predecessor_account_id();
+        assert!(
+            user.account_id == caller || self.is_contract_owner(&caller),
+            "Only user account can update this user."
+        );
+    }
+
+    /// Only company managers or contract owner may modify the company
+    fn assert_company_manager(&self, company: &Company) {
+        let caller = env::predecessor_account_id();
+        assert!(
+            company.managers.contains(&caller) || self.is_contract_owner(&caller),
+            "Only company managers can update this company."
+        );
+    }
+}
+
 /// Helper for init default test contract.
 impl Default for Contract {
     fn default() -> Self {
         let owner_id = env::predecessor_account_id();
-        Self {
-            owner_id: owner_id,
+        let contract = Self {
+            owner_id: Some(owner_id),
+            pending_owner_id: None,
+            roles: LookupMap::new(StorageKey::Roles),
             feedbacks: UnorderedMap::new(StorageKey::Feedbacks),
             users: UnorderedMap::new(StorageKey::Users),
+            user_ids_by_account: LookupMap::new(StorageKey::UserIdsByAccount),
+            votes: LookupMap::new(StorageKey::Votes),
+            feedbacks_by_company: LookupMap::new(StorageKey::FeedbacksByCompany),
+            feedbacks_by_user: LookupMap::new(StorageKey::FeedbacksByUser),
+            feedbacks_by_parent: LookupMap::new(StorageKey::FeedbacksByParent),
+            feedback_counts: LookupMap::new(StorageKey::FeedbackCounts),
+            storage_accounts: LookupMap::new(StorageKey::StorageAccounts),
+            record_payments: LookupMap::new(StorageKey::RecordPayments),
+            accounted_storage: 0,
             companies: UnorderedMap::new(StorageKey::Companies),
+            active_company_count: 0,
+            rating_scale: RatingScale::default(),
+            rating_histograms: LookupMap::new(StorageKey::RatingHistograms),
+            report_threshold: moderation::DEFAULT_REPORT_THRESHOLD,
+            moderation_queue: IdQueue::new(
+                StorageKey::ModerationQueue,
+                StorageKey::ModerationQueueOrder,
+            ),
+            report_resolutions: LookupMap::new(StorageKey::ReportResolutions),
+            moderation_log: Vector::new(StorageKey::ModerationLog),
+            moderation_log_by_target: LookupMap::new(StorageKey::ModerationLogByTarget),
+            appeals: LookupMap::new(StorageKey::Appeals),
+            open_appeals: IdQueue::new(StorageKey::OpenAppeals, StorageKey::OpenAppealsOrder),
+            claims: LookupMap::new(StorageKey::Claims),
+            claim_ids: LookupMap::new(StorageKey::ClaimIds),
+            pending_claims: IdQueue::new(StorageKey::PendingClaims, StorageKey::PendingClaimsOrder),
+            official_responses: LookupMap::new(StorageKey::OfficialResponses),
+            company_ids_by_name: LookupMap::new(StorageKey::CompanyIdsByName),
+            company_redirects: LookupMap::new(StorageKey::CompanyRedirects),
+            migration: None,
             next_feedback_id: 0,
             next_user_id: 0,
             next_company_id: 0,
-        }
+            next_claim_id: 0,
+        };
+        migration::write_state_version();
+
+        contract
     }
 }
 
 #[cfg(all(test, not(target_arch = "wasm32")))]
-#[cfg(test)]
 mod tests {
     use super::*;
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::json_types::ValidAccountId;
     use near_sdk::MockedBlockchain;
     use near_sdk::{testing_env, VMContext};
+    use std::convert::TryInto;
     const CURRENT_ACCOUNT_ADDRESS: &str = "alice_near";
     const SIGNER_ACCOUNT_ADDRESS: &str = "bob_near";
     const PREDECESSOR_ACCOUNT_ADDRESS: &str = "carol_near";
+    // One NEAR, attached to every call unless a test says otherwise
+    const STORAGE_DEPOSIT: u128 = 1_000_000_000_000_000_000_000_000;
 
     fn catch_unwind_silent<F: FnOnce() -> R + panic::UnwindSafe, R>(
         f: F,
@@ -542,7 +1873,7 @@ mod tests {
             account_balance: 0,
             account_locked_balance: 0,
             storage_usage: 0,
-            attached_deposit: 0,
+            attached_deposit: STORAGE_DEPOSIT,
             prepaid_gas: 10u64.pow(18),
             random_seed: vec![0, 1, 2],
             is_view,
@@ -551,12 +1882,30 @@ mod tests {
         }
     }
 
+    fn set_predecessor(account_id: &str) {
+        let mut context = get_context(vec![], false);
+        context.predecessor_account_id = account_id.to_string();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+    }
+
+    // Register caller user and an active company for feedback tests
+    fn register_user_and_company(contract: &mut Contract) -> (User, Company) {
+        let user = contract.create_user(User::mock());
+        let company = contract.create_company(Company::mock());
+        let company = contract.update_active_company(company.id, true, None);
+        (user, company)
+    }
+
     #[test]
     fn test_owner_contract() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let contract = Contract::default();
-        assert_eq!(contract.owner_id, PREDECESSOR_ACCOUNT_ADDRESS.to_string());
+        assert_eq!(
+            contract.owner_id,
+            Some(PREDECESSOR_ACCOUNT_ADDRESS.to_string())
+        );
     }
 
     // Test fn create_feedback
@@ -565,6 +1914,7 @@ mod tests {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
         let feedback = Feedback::mock();
         let new_feedback = contract.create_feedback(feedback.clone());
 
@@ -574,372 +1924,2785 @@ mod tests {
         );
     }
 
-    // Test fn update_feedback
+    // Test fn create_feedback derives user_id from caller
     #[test]
-    fn test_update_feedback() {
+    fn test_create_feedback_derives_user() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
 
-        let mock_feedback = Feedback::mock();
-        let first_feedback = contract.create_feedback(mock_feedback.clone());
+        set_predecessor("dave_near");
+        let author = contract.create_user(User::mock());
+        let mut mock_feedback = Feedback::mock();
+        mock_feedback.user_id = 0;
+        let first_feedback = contract.create_feedback(mock_feedback);
         assert_eq!(
-            first_feedback.content, mock_feedback.content,
-            "first_feedback is not equal"
+            first_feedback.user_id, author.id,
+            "first_feedback user_id should be caller user"
         );
+    }
 
-        let mut mock_feedback_2 = Feedback::mock();
-        mock_feedback_2.content = "feedback2".to_string();
-
-        let first_feedback_updated =
-            contract.update_feedback(first_feedback.id, mock_feedback_2.clone());
+    // Test fn create_feedback fail when caller has no user
+    #[test]
+    fn test_fail_create_feedback_without_user() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
 
-        assert_eq!(
-            first_feedback_updated.content, mock_feedback_2.content,
-            "first_feedback_updated is not equal"
-        );
+        set_predecessor("dave_near");
+        catch_unwind_silent(move || {
+            contract.create_feedback(Feedback::mock());
+        })
+        .unwrap_err();
     }
 
+    // Test fn create_feedback fail when caller user is deactivated
     #[test]
-    fn test_fail_update_feedback() {
+    fn test_fail_create_feedback_inactive_user() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        let (user, _) = register_user_and_company(&mut contract);
+        contract.update_active_user(user.id, false, None);
 
         catch_unwind_silent(move || {
-            contract.update_feedback(99999, Feedback::mock().clone());
+            contract.create_feedback(Feedback::mock());
         })
         .unwrap_err();
     }
 
-    // Test fn get_feedback
+    // Test fn create_feedback fail when company does not exist
     #[test]
-    fn test_get_feedback() {
+    fn test_fail_create_feedback_unknown_company() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
 
-        let mock_feedback = Feedback::mock();
-        let first_feedback = contract.create_feedback(mock_feedback.clone());
-        assert_eq!(
-            first_feedback.content, mock_feedback.content,
-            "first_feedback is not equal"
-        );
-
-        let get_feedback = contract.get_feedback(first_feedback.id);
-        assert_eq!(
-            get_feedback.content, mock_feedback.content,
-            "get_feedback not found"
-        );
+        let mut mock_feedback = Feedback::mock();
+        mock_feedback.company_id = 99999;
+        catch_unwind_silent(move || {
+            contract.create_feedback(mock_feedback);
+        })
+        .unwrap_err();
     }
 
-    // Test fn get_feedbacks
+    // Test fn create_feedback fail when company is deactivated
     #[test]
-    fn test_get_feedbacks() {
+    fn test_fail_create_feedback_inactive_company() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        let (_, company) = register_user_and_company(&mut contract);
+        contract.update_active_company(company.id, false, None);
 
-        let mock_feedback = Feedback::mock();
-        let first_feedback = contract.create_feedback(mock_feedback.clone());
-        assert_eq!(
-            first_feedback.content, mock_feedback.content,
-            "first_feedback is not equal"
-        );
+        catch_unwind_silent(move || {
+            contract.create_feedback(Feedback::mock());
+        })
+        .unwrap_err();
+    }
 
-        let mock_feedback_2 = Feedback::mock();
-        let second_feedback = contract.create_feedback(mock_feedback_2.clone());
-        assert_eq!(
-            second_feedback.content, mock_feedback_2.content,
-            "second_feedback is not equal"
-        );
+    // Test fn create_feedback fail when parent does not exist
+    #[test]
+    fn test_fail_create_feedback_unknown_parent() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
 
-        let get_feedbacks = contract.get_feedbacks(0, 10);
-        assert_eq!(get_feedbacks.len(), 2, "get_feedbacks not found");
+        let mut mock_feedback = Feedback::mock();
+        mock_feedback.parent_id = Some(99999);
+        catch_unwind_silent(move || {
+            contract.create_feedback(mock_feedback);
+        })
+        .unwrap_err();
     }
 
-    // Test fn update_active_feedback
+    // Test fn create_feedback fail when reply is on another company or an inactive parent
     #[test]
-    fn test_update_active_feedback() {
+    fn test_fail_create_reply_mismatched_parent() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+        let mut mock_company = Company::mock();
+        mock_company.name = "Other Company".to_string();
+        let other_company = contract.create_company(mock_company);
+        contract.update_active_company(other_company.id, true, None);
 
-        let mock_feedback = Feedback::mock();
-        let first_feedback = contract.create_feedback(mock_feedback.clone());
-        assert_eq!(
-            first_feedback.activate, false,
-            "feedback should be deactivate by default"
-        );
+        let first_feedback = contract.create_feedback(Feedback::mock());
+        let mut mock_reply = Feedback::mock();
+        mock_reply.parent_id = Some(first_feedback.id);
+        mock_reply.rating = 0;
+        mock_reply.company_id = other_company.id;
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.create_feedback(mock_reply.clone());
+        }))
+        .unwrap_err();
 
-        let updated_feedback = contract.update_active_feedback(first_feedback.id, true);
-        assert_eq!(
-            updated_feedback.activate, true,
-            "feedback should be activate after update"
-        );
+        mock_reply.company_id = first_feedback.company_id;
+        contract.update_active_feedback(first_feedback.id, false, None);
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.create_feedback(mock_reply.clone());
+        }))
+        .unwrap_err();
     }
 
-    // Test fn get_feedbacks_by_user_id_paging
+    // Test fn create_feedback fail when reply carries a reaction
     #[test]
-    fn test_get_feedbacks_by_user_id_paging() {
+    fn test_fail_create_feedback_with_reaction() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
 
-        let mut mock_feedback = Feedback::mock();
+        let first_feedback = contract.create_feedback(Feedback::mock());
+        let mut mock_reply = Feedback::mock();
+        mock_reply.parent_id = Some(first_feedback.id);
+        mock_reply.reaction = Reaction::Like;
+        catch_unwind_silent(move || {
+            contract.create_feedback(mock_reply);
+        })
+        .unwrap_err();
+    }
+
+    // Test fn vote counts one vote per account
+    #[test]
+    fn test_vote() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        let first_feedback = contract.create_feedback(Feedback::mock());
+        for (account_id, reaction) in [
+            ("dave_near", Reaction::Like),
+            ("eve_near", Reaction::Like),
+            ("frank_near", Reaction::Dislike),
+            ("grace_near", Reaction::Report),
+        ] {
+            set_predecessor(account_id);
+            contract.vote(first_feedback.id, reaction);
+        }
+
+        set_predecessor("dave_near");
+        let voted_feedback = contract.vote(first_feedback.id, Reaction::Like);
+        assert_eq!(voted_feedback.up_vote, 2, "up_vote is not correct");
+        assert_eq!(voted_feedback.down_vote, 1, "down_vote is not correct");
+        assert_eq!(voted_feedback.report_vote, 1, "report_vote is not correct");
+        assert_eq!(
+            contract.get_vote(first_feedback.id, "dave_near".to_string()),
+            Some(Reaction::Like)
+        );
+        assert_eq!(
+            contract.feedbacks.len(),
+            1,
+            "vote should not create feedback"
+        );
+    }
+
+    // Test fn vote changes previous vote
+    #[test]
+    fn test_vote_change() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        let first_feedback = contract.create_feedback(Feedback::mock());
+        contract.vote(first_feedback.id, Reaction::Like);
+        let voted_feedback = contract.vote(first_feedback.id, Reaction::Dislike);
+        assert_eq!(voted_feedback.up_vote, 0, "up_vote is not correct");
+        assert_eq!(voted_feedback.down_vote, 1, "down_vote is not correct");
+    }
+
+    // Test fn unvote
+    #[test]
+    fn test_unvote() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        let first_feedback = contract.create_feedback(Feedback::mock());
+        contract.vote(first_feedback.id, Reaction::Report);
+        let unvoted_feedback = contract.unvote(first_feedback.id);
+        assert_eq!(
+            unvoted_feedback.report_vote, 0,
+            "report_vote is not correct"
+        );
+        assert_eq!(
+            contract.get_vote(first_feedback.id, PREDECESSOR_ACCOUNT_ADDRESS.to_string()),
+            None
+        );
+
+        catch_unwind_silent(move || {
+            contract.unvote(first_feedback.id);
+        })
+        .unwrap_err();
+    }
+
+    // Test fn vote fail without reaction or feedback
+    #[test]
+    fn test_fail_vote() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        let first_feedback = contract.create_feedback(Feedback::mock());
+        catch_unwind_silent(|| {
+            Contract::default().vote(99999, Reaction::Like);
+        })
+        .unwrap_err();
+        catch_unwind_silent(move || {
+            contract.vote(first_feedback.id, Reaction::None);
+        })
+        .unwrap_err();
+    }
+
+    // Test Reaction rejects unknown values at the API boundary
+    #[test]
+    fn test_reaction_json() {
+        let reaction: Reaction = serde_json::from_str("\"Dislike\"").unwrap();
+        assert_eq!(reaction, Reaction::Dislike);
+        assert!(serde_json::from_str::<Reaction>("\"Ban\"").is_err());
+        assert!(serde_json::from_str::<Reaction>("2").is_err());
+    }
+
+    // Test fn update_feedback
+    #[test]
+    fn test_update_feedback() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        let mock_feedback = Feedback::mock();
+        let first_feedback = contract.create_feedback(mock_feedback.clone());
+        assert_eq!(
+            first_feedback.content, mock_feedback.content,
+            "first_feedback is not equal"
+        );
+
+        let patch = FeedbackPatch {
+            content: Some("feedback2".to_string()),
+            ..Default::default()
+        };
+
+        let first_feedback_updated = contract.update_feedback(first_feedback.id, patch);
+
+        assert_eq!(
+            first_feedback_updated.content, "feedback2",
+            "first_feedback_updated is not equal"
+        );
+        assert_eq!(
+            first_feedback_updated.rating, first_feedback.rating,
+            "rating should not change when not patched"
+        );
+    }
+
+    // Test fn update_feedback keeps server-controlled fields
+    #[test]
+    fn test_update_feedback_keeps_counters() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        let first_feedback = contract.create_feedback(Feedback::mock());
+        contract.vote(first_feedback.id, Reaction::Like);
+        assert_eq!(contract.get_feedback(first_feedback.id).up_vote, 1);
+
+        let patch = FeedbackPatch {
+            content: Some("feedback2".to_string()),
+            rating: Some(3),
+        };
+        let first_feedback_updated = contract.update_feedback(first_feedback.id, patch);
+        assert_eq!(first_feedback_updated.id, first_feedback.id);
+        assert_eq!(first_feedback_updated.rating, 3);
+        assert_eq!(
+            first_feedback_updated.up_vote, 1,
+            "up_vote should not reset"
+        );
+        assert_eq!(first_feedback_updated.create_at, first_feedback.create_at);
+        assert!(first_feedback_updated.activate);
+    }
+
+    #[test]
+    fn test_fail_update_feedback() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        catch_unwind_silent(move || {
+            contract.update_feedback(99999, FeedbackPatch::default());
+        })
+        .unwrap_err();
+    }
+
+    // Test fn update_feedback by author
+    #[test]
+    fn test_update_feedback_by_author() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        set_predecessor("dave_near");
+        let author = contract.create_user(User::mock());
+        let mut mock_feedback = Feedback::mock();
+        mock_feedback.user_id = author.id;
+        let first_feedback = contract.create_feedback(mock_feedback.clone());
+
+        let patch = FeedbackPatch {
+            content: Some("feedback2".to_string()),
+            ..Default::default()
+        };
+        let first_feedback_updated = contract.update_feedback(first_feedback.id, patch);
+        assert_eq!(
+            first_feedback_updated.content, "feedback2",
+            "first_feedback_updated is not equal"
+        );
+    }
+
+    // Test fn update_feedback fail when caller is not the author
+    #[test]
+    fn test_fail_update_feedback_not_author() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        set_predecessor("dave_near");
+        let author = contract.create_user(User::mock());
+        let mut mock_feedback = Feedback::mock();
+        mock_feedback.user_id = author.id;
+        let first_feedback = contract.create_feedback(mock_feedback.clone());
+
+        set_predecessor("eve_near");
+        contract.create_user(User::mock());
+        catch_unwind_silent(move || {
+            contract.update_feedback(first_feedback.id, FeedbackPatch::default());
+        })
+        .unwrap_err();
+    }
+
+    // Test fn get_feedback
+    #[test]
+    fn test_get_feedback() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        let mock_feedback = Feedback::mock();
+        let first_feedback = contract.create_feedback(mock_feedback.clone());
+        assert_eq!(
+            first_feedback.content, mock_feedback.content,
+            "first_feedback is not equal"
+        );
+
+        let get_feedback = contract.get_feedback(first_feedback.id);
+        assert_eq!(
+            get_feedback.content, mock_feedback.content,
+            "get_feedback not found"
+        );
+    }
+
+    // Test fn get_feedbacks
+    #[test]
+    fn test_get_feedbacks() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        let mock_feedback = Feedback::mock();
+        let first_feedback = contract.create_feedback(mock_feedback.clone());
+        assert_eq!(
+            first_feedback.content, mock_feedback.content,
+            "first_feedback is not equal"
+        );
+
+        let mock_feedback_2 = Feedback::mock();
+        let second_feedback = contract.create_feedback(mock_feedback_2.clone());
+        assert_eq!(
+            second_feedback.content, mock_feedback_2.content,
+            "second_feedback is not equal"
+        );
+
+        let get_feedbacks = contract.get_feedbacks(None, 10, None).items;
+        assert_eq!(get_feedbacks.len(), 2, "get_feedbacks not found");
+    }
+
+    // Test fn get_feedbacks cuts a page short after a run of inactive feedbacks
+    #[test]
+    fn test_get_feedbacks_inactive_run() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        let count = MAX_SKIPPED_ROWS + 10;
+        for _ in 0..count {
+            // Fresh context per call keeps event logs under the limit
+            set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+            let feedback = contract.create_feedback(Feedback::mock());
+            if feedback.id > 0 {
+                contract.update_active_feedback(feedback.id, false, None);
+            }
+        }
+
+        let page = contract.get_feedbacks(None, 1, None);
+        assert!(page.items.is_empty(), "page should be cut short");
+        let cursor = page.next_cursor.expect("short page should have a cursor");
+        assert_eq!(cursor.id, count - 1 - MAX_SKIPPED_ROWS);
+        let page = contract.get_feedbacks(Some(cursor), 1, None);
+        assert_eq!(page.items[0].id, 0);
+        assert_eq!(page.total, 1);
+        assert_eq!(page.next_cursor, None);
+    }
+
+    // Test fn update_active_feedback
+    #[test]
+    fn test_update_active_feedback() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        let mock_feedback = Feedback::mock();
+        let first_feedback = contract.create_feedback(mock_feedback.clone());
+        assert!(
+            first_feedback.activate,
+            "feedback should be activate by default"
+        );
+
+        let updated_feedback = contract.update_active_feedback(first_feedback.id, false, None);
+        assert!(
+            !updated_feedback.activate,
+            "feedback should be deactivate after update"
+        );
+    }
+
+    // Test fn get_feedbacks_by_user_id_paging
+    #[test]
+    fn test_get_feedbacks_by_user_id_paging() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        let mut mock_feedback = Feedback::mock();
+
+        let (first_user, _) = register_user_and_company(&mut contract);
+
+        mock_feedback.user_id = first_user.id;
+
+        let first_feedback = contract.create_feedback(mock_feedback.clone());
+        contract.update_active_feedback(first_feedback.id, true, None);
+
+        assert_eq!(
+            first_feedback.user_id, first_user.id,
+            "first_feedback user_id is not correct"
+        );
+
+        let get_feedbacks = contract
+            .get_feedbacks_by_user_id_paging(first_user.id, None, 10)
+            .items;
+
+        assert_eq!(get_feedbacks.len(), 1, "get_feedbacks not found");
+
+        let second_feedback = contract.create_feedback(mock_feedback.clone());
+        let first_page = contract.get_feedbacks_by_user_id_paging(first_user.id, None, 1);
+        assert_eq!(first_page.total, 2, "total is not correct");
+        assert_eq!(first_page.items[0].id, second_feedback.id);
+        let second_page =
+            contract.get_feedbacks_by_user_id_paging(first_user.id, first_page.next_cursor, 1);
+        assert_eq!(second_page.items[0].id, first_feedback.id);
+        assert!(second_page.next_cursor.is_none(), "last page has no cursor");
+
+        for feedback in get_feedbacks {
+            assert_eq!(
+                feedback.user_id, first_user.id,
+                "feedback user_id is not correct"
+            );
+        }
+    }
+
+    // Test fn get_feedbacks_by_parent_id_paging
+    #[test]
+    fn test_get_feedbacks_by_parent_id_paging() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        let mut mock_feedback = Feedback::mock();
+
+        let first_feedback = contract.create_feedback(mock_feedback.clone());
+        contract.update_active_feedback(first_feedback.id, true, None);
+
+        mock_feedback.parent_id = Some(first_feedback.id);
+        mock_feedback.rating = 0;
+        let second_feedback = contract.create_feedback(mock_feedback.clone());
+        contract.update_active_feedback(second_feedback.id, true, None);
+
+        let feedbacks = contract
+            .get_feedbacks_by_parent_id_paging(first_feedback.id, None, 10)
+            .items;
+
+        assert_eq!(feedbacks.len(), 1, "feedbacks not found");
+        assert_eq!(
+            feedbacks[0].parent_id,
+            Some(first_feedback.id),
+            "feedback id is not correct"
+        );
+
+        let third_feedback = contract.create_feedback(mock_feedback.clone());
+        contract.update_active_feedback(third_feedback.id, true, None);
+        let feedbacks = contract
+            .get_feedbacks_by_parent_id_paging(first_feedback.id, None, 10)
+            .items;
+
+        assert_eq!(feedbacks.len(), 2, "feedbacks not found");
+        assert_eq!(
+            feedbacks[1].parent_id,
+            Some(first_feedback.id),
+            "third_feedback parent_id is not correct"
+        );
+
+        for feedback in feedbacks {
+            assert_eq!(
+                feedback.parent_id,
+                Some(first_feedback.id),
+                "feedback parent_id is not correct"
+            );
+        }
+    }
+
+    // Test fn get_root_feedbacks_by_company
+    #[test]
+    fn test_get_root_feedbacks_by_company() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        let (_, first_company) = register_user_and_company(&mut contract);
+
+        let first_feedback = contract.create_feedback(Feedback::mock());
+        let mut mock_reply = Feedback::mock();
+        mock_reply.parent_id = Some(first_feedback.id);
+        mock_reply.rating = 0;
+        contract.create_feedback(mock_reply);
+        contract.create_feedback(Feedback::mock());
+
+        let feedbacks = contract
+            .get_root_feedbacks_by_company(first_company.id, None, 10, None)
+            .items;
+        assert_eq!(feedbacks.len(), 2, "root feedbacks not found");
+        for feedback in feedbacks {
+            assert!(feedback.parent_id.is_none(), "feedback should be root");
+        }
+        assert_eq!(
+            contract.get_feedback(first_feedback.id).up_vote,
+            0,
+            "root review should not vote on feedback 0"
+        );
+    }
+
+    // Test company rating summary
+    #[test]
+    fn test_company_average_rating() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        let (_, first_company) = register_user_and_company(&mut contract);
+
+        let mut first_feedback = None;
+        for rating in [5, 3, 4] {
+            let mut mock_feedback = Feedback::mock();
+            mock_feedback.rating = rating;
+            first_feedback.get_or_insert(contract.create_feedback(mock_feedback));
+        }
+        let mut mock_reply = Feedback::mock();
+        mock_reply.parent_id = first_feedback.map(|feedback| feedback.id);
+        mock_reply.rating = 0;
+        contract.create_feedback(mock_reply);
+
+        let company = contract.get_company(first_company.id);
+        assert_eq!(company.rating_count, 3, "rating_count is not correct");
+        assert!((company.average_rating - 4.0).abs() < 1e-9);
+        assert_eq!(company.rating_histogram, vec![0, 0, 1, 1, 1]);
+    }
+
+    // Test fn update_feedback moves rating in histogram
+    #[test]
+    fn test_update_feedback_rating() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        let (_, first_company) = register_user_and_company(&mut contract);
+
+        let first_feedback = contract.create_feedback(Feedback::mock());
+        let patch = FeedbackPatch {
+            rating: Some(2),
+            ..Default::default()
+        };
+        contract.update_feedback(first_feedback.id, patch);
+
+        let company = contract.get_company(first_company.id);
+        assert_eq!(company.rating_count, 1, "rating_count is not correct");
+        assert!((company.average_rating - 2.0).abs() < 1e-9);
+        assert_eq!(company.rating_histogram, vec![0, 1, 0, 0, 0]);
+    }
+
+    // Test fn update_active_feedback removes and restores rating
+    #[test]
+    fn test_update_active_feedback_rating() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        let (_, first_company) = register_user_and_company(&mut contract);
+
+        let first_feedback = contract.create_feedback(Feedback::mock());
+        contract.update_active_feedback(first_feedback.id, false, None);
+        let company = contract.get_company(first_company.id);
+        assert_eq!(
+            company.rating_count, 0,
+            "deactivated rating should be removed"
+        );
+        assert_eq!(company.rating_histogram, vec![0, 0, 0, 0, 0]);
+
+        // Editing a deactivated review must not count it again
+        let patch = FeedbackPatch {
+            rating: Some(3),
+            ..Default::default()
+        };
+        contract.update_feedback(first_feedback.id, patch);
+        assert_eq!(contract.get_company(first_company.id).rating_count, 0);
+
+        contract.update_active_feedback(first_feedback.id, true, None);
+        contract.update_active_feedback(first_feedback.id, true, None);
+        let company = contract.get_company(first_company.id);
+        assert_eq!(
+            company.rating_count, 1,
+            "reactivated rating should be restored"
+        );
+        assert!((company.average_rating - 3.0).abs() < 1e-9);
+        assert_eq!(company.rating_histogram, vec![0, 0, 1, 0, 0]);
+    }
+
+    // Test company rating aggregates over random operation sequences
+    #[test]
+    fn test_company_rating_random_operations() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+        let mut mock_company = Company::mock();
+        mock_company.name = "Second Company".to_string();
+        let second_company = contract.create_company(mock_company);
+        contract.update_active_company(second_company.id, true, None);
+
+        // xorshift keeps the sequence reproducible without extra dependencies
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % bound as u64) as usize
+        };
+
+        for _ in 0..300 {
+            // Fresh context per operation so gas does not accumulate
+            set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+            let count = contract.next_feedback_id;
+            match next(5) {
+                0 => {
+                    let mut mock_feedback = Feedback::mock();
+                    mock_feedback.company_id = next(2);
+                    mock_feedback.rating = 1 + next(5);
+                    contract.create_feedback(mock_feedback);
+                }
+                1 if count > 0 => {
+                    let parent_feedback = contract.get_feedback(next(count));
+                    if parent_feedback.activate {
+                        let mut mock_reply = Feedback::mock();
+                        mock_reply.company_id = parent_feedback.company_id;
+                        mock_reply.parent_id = Some(parent_feedback.id);
+                        mock_reply.rating = 0;
+                        contract.create_feedback(mock_reply);
+                    }
+                }
+                2 if count > 0 => {
+                    let feedback = contract.get_feedback(next(count));
+                    if feedback.parent_id.is_none() {
+                        let patch = FeedbackPatch {
+                            rating: Some(1 + next(5)),
+                            ..Default::default()
+                        };
+                        contract.update_feedback(feedback.id, patch);
+                    }
+                }
+                3 | 4 if count > 0 => {
+                    contract.update_active_feedback(next(count), next(2) == 0, None);
+                }
+                _ => {}
+            }
+
+            for company_id in 0..2 {
+                let mut histogram = vec![0; 5];
+                for feedback in contract.feedbacks.values().map(Feedback::from) {
+                    if feedback.company_id == company_id
+                        && feedback.parent_id.is_none()
+                        && feedback.activate
+                    {
+                        histogram[feedback.rating - 1] += 1;
+                    }
+                }
+                let rating_count: usize = histogram.iter().sum();
+                let rating: usize = histogram
+                    .iter()
+                    .enumerate()
+                    .map(|(index, count)| (index + 1) * count)
+                    .sum();
+
+                let company = contract.company(company_id).unwrap();
+                assert_eq!(company.rating, rating, "rating sum is not consistent");
+                assert_eq!(
+                    company.rating_count, rating_count,
+                    "rating_count is not consistent"
+                );
+                assert_eq!(
+                    contract.get_company(company_id).rating_histogram,
+                    histogram,
+                    "rating_histogram is not consistent"
+                );
+            }
+        }
+    }
+
+    // Test fn create_feedback fail when rating is out of scale
+    #[test]
+    fn test_fail_create_feedback_rating_out_of_scale() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        for rating in [0, 6, 1_000_000] {
+            let mut mock_feedback = Feedback::mock();
+            mock_feedback.rating = rating;
+            catch_unwind_silent(panic::AssertUnwindSafe(|| {
+                contract.create_feedback(mock_feedback);
+            }))
+            .unwrap_err();
+        }
+    }
+
+    // Test fn create_feedback fail when reply carries a rating
+    #[test]
+    fn test_fail_create_reply_with_rating() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+
+        let first_feedback = contract.create_feedback(Feedback::mock());
+        let mut mock_reply = Feedback::mock();
+        mock_reply.parent_id = Some(first_feedback.id);
+        catch_unwind_silent(move || {
+            contract.create_feedback(mock_reply);
+        })
+        .unwrap_err();
+    }
+
+    // Test fn set_rating_scale
+    #[test]
+    fn test_set_rating_scale() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        let (_, first_company) = register_user_and_company(&mut contract);
+
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.set_rating_scale(5, 1);
+        }))
+        .unwrap_err();
+        contract.set_rating_scale(1, 10);
+        assert_eq!(contract.get_rating_scale(), RatingScale { min: 1, max: 10 });
+
+        let mut mock_feedback = Feedback::mock();
+        mock_feedback.rating = 9;
+        contract.create_feedback(mock_feedback);
+        let company = contract.get_company(first_company.id);
+        assert_eq!(company.rating_histogram.len(), 10);
+        assert_eq!(company.rating_histogram[8], 1);
+
+        // Scale is fixed once ratings are stored
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.set_rating_scale(1, 5);
+        }))
+        .unwrap_err();
+        set_predecessor("eve_near");
+        catch_unwind_silent(move || {
+            contract.set_rating_scale(1, 5);
+        })
+        .unwrap_err();
+    }
+
+    // Test fn get_feedbacks_by_company_id_paging
+    #[test]
+    fn test_get_feedbacks_by_company_id_paging() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        // Create active company
+        let (_, first_company) = register_user_and_company(&mut contract);
+
+        // Create feedback by company
+        let mut mock_feedback = Feedback::mock();
+        mock_feedback.company_id = first_company.id;
+        let first_feedback = contract.create_feedback(mock_feedback.clone());
+        contract.update_active_feedback(first_feedback.id, true, None);
+
+        // Verify feedback by company id
+        let feedbacks = contract
+            .get_feedbacks_by_company_id_paging(first_company.id, None, 10, None)
+            .items;
+        assert_eq!(feedbacks.len(), 1, "feedbacks not found");
+        assert_eq!(
+            feedbacks[0].company_id, first_company.id,
+            "feedback company_id is not correct"
+        );
+
+        // Create feedback by company
+        let second_feedback = contract.create_feedback(mock_feedback.clone());
+        contract.update_active_feedback(second_feedback.id, true, None);
+
+        // Verify feedback by company id
+        let feedbacks = contract
+            .get_feedbacks_by_company_id_paging(first_company.id, None, 10, None)
+            .items;
+        assert_eq!(feedbacks.len(), 2, "feedbacks not found");
+        assert_eq!(
+            feedbacks[1].company_id, first_company.id,
+            "feedback company_id is not correct"
+        );
+    }
+
+    // Test feedback index paging is newest first and skips inactive feedbacks
+    #[test]
+    fn test_feedback_index_paging() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        let (first_user, first_company) = register_user_and_company(&mut contract);
+
+        for _ in 0..5 {
+            contract.create_feedback(Feedback::mock());
+        }
+        contract.update_active_feedback(3, false, None);
+
+        let ids = |page: &Page<FeedbackView>| -> Vec<FeedbackId> {
+            page.items.iter().map(|feedback| feedback.id).collect()
+        };
+        let first_page =
+            contract.get_feedbacks_by_company_id_paging(first_company.id, None, 2, None);
+        assert_eq!(ids(&first_page), vec![4, 2]);
+        assert_eq!(first_page.total, 4, "total is not correct");
+        let cursor = first_page.next_cursor.expect("next_cursor not found");
+        assert_eq!(cursor.id, 2, "next_cursor is not correct");
+
+        // New feedback does not shift the following page
+        contract.create_feedback(Feedback::mock());
+        let second_page =
+            contract.get_feedbacks_by_company_id_paging(first_company.id, Some(cursor), 2, None);
+        assert_eq!(ids(&second_page), vec![1, 0]);
+        assert_eq!(second_page.total, 5, "total is not correct");
+        assert!(second_page.next_cursor.is_none(), "last page has no cursor");
+
+        let user_page = contract.get_feedbacks_by_user_id_paging(first_user.id, Some(cursor), 10);
+        assert_eq!(ids(&user_page), vec![1, 0]);
+        let global_page = contract.get_feedbacks(Some(cursor), 1, None);
+        assert_eq!(ids(&global_page), vec![1]);
+        assert_eq!(global_page.total, 5, "total is not correct");
+
+        // Reactivation counts feedback again, repeated updates do not
+        contract.update_active_feedback(3, true, None);
+        contract.update_active_feedback(3, true, None);
+        assert_eq!(contract.get_feedbacks(None, 10, None).total, 6);
+
+        let empty_page = contract.get_feedbacks_by_company_id_paging(99999, None, 10, None);
+        assert!(empty_page.items.is_empty());
+        assert_eq!(empty_page.total, 0);
+    }
+
+    // Test feedback queries only read the newest feedbacks of a listing
+    #[test]
+    fn test_feedback_query_scan_limit() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        let (_, first_company) = register_user_and_company(&mut contract);
+
+        for id in 0..MAX_QUERY_SCAN + 1 {
+            // Fresh context per call keeps event logs under the limit
+            set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+            let mut mock_feedback = Feedback::mock();
+            mock_feedback.rating = if id == 0 { 1 } else { 5 };
+            contract.create_feedback(mock_feedback);
+        }
+
+        let query = FeedbackQuery {
+            max_rating: Some(1),
+            ..Default::default()
+        };
+        let page = contract.get_feedbacks(None, 10, Some(query));
+        assert_eq!(page.total, 0);
+        assert!(page.truncated, "page should be marked truncated");
+        let page =
+            contract.get_feedbacks_by_company_id_paging(first_company.id, None, 10, Some(query));
+        assert!(page.items.is_empty(), "oldest feedback should not be read");
+        let query = FeedbackQuery {
+            min_rating: Some(5),
+            ..Default::default()
+        };
+        let page = contract.get_feedbacks(None, 10, Some(query));
+        assert_eq!(page.total, MAX_QUERY_SCAN, "total is not correct");
+        assert!(page.truncated, "page should be marked truncated");
+    }
+
+    // Test feedback query sorting, filters and cursors
+    #[test]
+    fn test_feedback_query() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = Contract::default();
+        let (_, first_company) = register_user_and_company(&mut contract);
+
+        // Ratings 2, 5, 3, 5 one hour apart
+        for rating in [2, 5, 3, 5] {
+            context.block_timestamp += 3_600_000_000_000;
+            context.storage_usage = env::storage_usage();
+            testing_env!(context.clone());
+            let mut mock_feedback = Feedback::mock();
+            mock_feedback.rating = rating;
+            contract.create_feedback(mock_feedback);
+        }
+        for voter in ["alice", "bob"] {
+            set_predecessor(voter);
+            contract.vote(0, Reaction::Like);
+        }
+        set_predecessor("alice");
+        contract.vote(1, Reaction::Like);
+        contract.vote(3, Reaction::Report);
+
+        let ids = |page: &Page<FeedbackView>| -> Vec<FeedbackId> {
+            page.items.iter().map(|feedback| feedback.id).collect()
+        };
+        let sorted = |sort: FeedbackSort| {
+            let query = FeedbackQuery {
+                sort,
+                ..Default::default()
+            };
+            ids(&contract.get_feedbacks(None, 10, Some(query)))
+        };
+        assert_eq!(sorted(FeedbackSort::Newest), vec![3, 2, 1, 0]);
+        assert_eq!(sorted(FeedbackSort::Oldest), vec![0, 1, 2, 3]);
+        assert_eq!(sorted(FeedbackSort::HighestRating), vec![3, 1, 2, 0]);
+        assert_eq!(sorted(FeedbackSort::MostUpvoted), vec![0, 1, 3, 2]);
+        assert_eq!(sorted(FeedbackSort::Hot), vec![0, 3, 2, 1]);
+
+        // Cursor keeps query order across pages
+        let query = FeedbackQuery {
+            sort: FeedbackSort::HighestRating,
+            ..Default::default()
+        };
+        let first_page =
+            contract.get_feedbacks_by_company_id_paging(first_company.id, None, 3, Some(query));
+        assert_eq!(ids(&first_page), vec![3, 1, 2]);
+        let second_page = contract.get_feedbacks_by_company_id_paging(
+            first_company.id,
+            first_page.next_cursor,
+            3,
+            Some(query),
+        );
+        assert_eq!(ids(&second_page), vec![0]);
+        assert_eq!(second_page.total, 4, "total is not correct");
+        assert!(second_page.next_cursor.is_none(), "last page has no cursor");
+
+        // Filters narrow items and total
+        let query = FeedbackQuery {
+            min_rating: Some(3),
+            from: Some((2 * 3_600_000_000_000).into()),
+            ..Default::default()
+        };
+        let page = contract.get_feedbacks(None, 1, Some(query));
+        assert_eq!(ids(&page), vec![3]);
+        assert_eq!(page.total, 3, "total is not correct");
+        assert!(!page.truncated, "whole listing should be read");
+        let query = FeedbackQuery {
+            max_rating: Some(3),
+            to: Some((4 * 3_600_000_000_000).into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&contract.get_feedbacks(None, 10, Some(query))),
+            vec![2, 0]
+        );
+        // Only migrated replies carry a reaction of their own
+        let mut reply = contract.feedback(1).unwrap();
+        reply.reaction = Reaction::Like;
+        contract.save_feedback(&reply);
+        let reacted = |reaction: Reaction| {
+            let query = FeedbackQuery {
+                reaction: Some(reaction),
+                ..Default::default()
+            };
+            ids(&contract.get_root_feedbacks_by_company(first_company.id, None, 10, Some(query)))
+        };
+        assert_eq!(reacted(Reaction::Like), vec![1]);
+        assert!(
+            reacted(Reaction::Report).is_empty(),
+            "votes are not reactions"
+        );
+        assert_eq!(reacted(Reaction::None), vec![3, 2, 0]);
+    }
+
+    // Test fn get_companies_paging
+    #[test]
+    fn test_get_companies_paging() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+        for index in 0..2 {
+            context.block_timestamp += 1;
+            context.storage_usage = env::storage_usage();
+            testing_env!(context.clone());
+            let mut mock_company = Company::mock();
+            mock_company.name = format!("Company {}", index);
+            let company = contract.create_company(mock_company);
+            contract.update_active_company(company.id, true, None);
+        }
+
+        let first_page = contract.get_companies_paging(None, 2);
+        let ids: Vec<CompanyId> = first_page.items.iter().map(|company| company.id).collect();
+        assert_eq!(ids, vec![2, 1]);
+        assert_eq!(first_page.total, 3, "total is not correct");
+
+        // Edits don't move companies between pages
+        let patch = CompanyPatch {
+            address: Some("New address".to_string()),
+            ..Default::default()
+        };
+        contract.update_company(0, patch);
+
+        let second_page = contract.get_companies_paging(first_page.next_cursor, 2);
+        let ids: Vec<CompanyId> = second_page.items.iter().map(|company| company.id).collect();
+        assert_eq!(ids, vec![0]);
+        assert!(second_page.next_cursor.is_none(), "last page has no cursor");
+
+        contract.update_active_company(1, false, None);
+        let page = contract.get_companies_paging(None, 10);
+        let ids: Vec<CompanyId> = page.items.iter().map(|company| company.id).collect();
+        assert_eq!(ids, vec![2, 0]);
+        assert_eq!(page.total, 2, "total is not correct");
+    }
+
+    // Test fn storage_deposit and storage_withdraw
+    #[test]
+    fn test_storage_deposit() {
+        let mut context = get_context(vec![], false);
+        context.account_balance = 10 * STORAGE_DEPOSIT;
+        testing_env!(context.clone());
+        let mut contract = Contract::default();
+        let dave = || -> ValidAccountId { "dave_near".try_into().unwrap() };
+        assert!(contract.storage_balance_of(dave()).is_none());
+
+        // Minimum balance covers a user and some feedbacks
+        let min_balance = contract.storage_balance_bounds().min.0;
+        let feedback_size = Feedback::mock().try_to_vec().unwrap().len() as u128;
+        assert!(
+            min_balance
+                > storage::MIN_STORAGE_FEEDBACKS as u128 * feedback_size * env::storage_byte_cost(),
+            "min balance is not correct"
+        );
+        assert!(contract.storage_balance_bounds().max.is_none());
+
+        // Registration below the minimum fails
+        context.attached_deposit = min_balance - 1;
+        testing_env!(context.clone());
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.storage_deposit(Some(dave()), None);
+        }))
+        .unwrap_err();
+
+        // Deposit for another account
+        context.attached_deposit = STORAGE_DEPOSIT;
+        testing_env!(context.clone());
+        let balance = contract.storage_deposit(Some(dave()), None);
+        assert_eq!(balance.total.0, STORAGE_DEPOSIT, "total is not correct");
+        assert!(
+            balance.available.0 < STORAGE_DEPOSIT,
+            "registration should be charged"
+        );
+        assert_eq!(
+            contract.storage_balance_of(dave()).unwrap().total.0,
+            STORAGE_DEPOSIT
+        );
+
+        context.predecessor_account_id = "dave_near".to_string();
+        context.attached_deposit = 1;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        let withdrawn = contract.storage_withdraw(Some(1000.into()));
+        assert_eq!(withdrawn.total.0, STORAGE_DEPOSIT - 1000);
+        let withdrawn = contract.storage_withdraw(None);
+        assert_eq!(withdrawn.available.0, 0, "available should be withdrawn");
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.storage_withdraw(Some(1.into()));
+        }))
+        .unwrap_err();
+    }
+
+    // Test fn storage_deposit with registration_only refunds past the minimum
+    #[test]
+    fn test_storage_registration_only() {
+        let mut context = get_context(vec![], false);
+        context.account_balance = 10 * STORAGE_DEPOSIT;
+        testing_env!(context);
+        let mut contract = Contract::default();
+        let dave = || -> ValidAccountId { "dave_near".try_into().unwrap() };
+        let min_balance = contract.storage_balance_bounds().min.0;
+
+        let balance = contract.storage_deposit(Some(dave()), Some(true));
+        assert_eq!(balance.total.0, min_balance, "total is not correct");
+        assert!(
+            balance.available.0 < min_balance,
+            "registration should be charged"
+        );
+        assert_eq!(near_sdk::test_utils::get_created_receipts().len(), 1);
+
+        // Registered accounts get the whole deposit back
+        let balance = contract.storage_deposit(Some(dave()), Some(true));
+        assert_eq!(balance.total.0, min_balance, "deposit should be refunded");
+        let balance = contract.storage_deposit(Some(dave()), None);
+        assert_eq!(balance.total.0, min_balance + STORAGE_DEPOSIT);
+    }
+
+    // Test fn storage_withdraw and storage_unregister require one yocto
+    #[test]
+    fn test_storage_one_yocto() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = Contract::default();
+        contract.storage_deposit(None, None);
+
+        for attached_deposit in [0, 2] {
+            context.attached_deposit = attached_deposit;
+            context.storage_usage = env::storage_usage();
+            testing_env!(context.clone());
+            catch_unwind_silent(panic::AssertUnwindSafe(|| {
+                contract.storage_withdraw(None);
+            }))
+            .unwrap_err();
+            catch_unwind_silent(panic::AssertUnwindSafe(|| {
+                contract.storage_unregister(None);
+            }))
+            .unwrap_err();
+        }
+    }
+
+    // Test fn storage_unregister returns the deposit once, only without data
+    #[test]
+    fn test_storage_unregister() {
+        let mut context = get_context(vec![], false);
+        context.account_balance = 10 * STORAGE_DEPOSIT;
+        testing_env!(context.clone());
+        let mut contract = Contract::default();
+        let carol = || -> ValidAccountId { PREDECESSOR_ACCOUNT_ADDRESS.try_into().unwrap() };
+        contract.storage_deposit(None, None);
+
+        context.attached_deposit = 1;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        assert!(contract.storage_unregister(None));
+        assert!(contract.storage_balance_of(carol()).is_none());
+        assert!(!contract.storage_unregister(None));
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.storage_unregister(Some(true));
+        }))
+        .unwrap_err();
+
+        // Accounts owning data cannot unregister
+        context.attached_deposit = STORAGE_DEPOSIT;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.create_user(User::mock());
+        context.attached_deposit = 1;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.storage_unregister(None);
+        }))
+        .unwrap_err();
+    }
+
+    // Test writes charge and refund the caller storage deposit
+    #[test]
+    fn test_storage_charged_on_write() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = Contract::default();
+        context.predecessor_account_id = "dave_near".to_string();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+
+        // Writes without deposit fail
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.create_user(User::mock());
+        }))
+        .unwrap_err();
+        // First writes must cover the minimum balance
+        context.attached_deposit = contract.storage_balance_bounds().min.0 - 1;
+        testing_env!(context.clone());
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.create_user(User::mock());
+        }))
+        .unwrap_err();
+
+        context.predecessor_account_id = PREDECESSOR_ACCOUNT_ADDRESS.to_string();
+        context.attached_deposit = STORAGE_DEPOSIT;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.storage_deposit(None, None);
+
+        // Later writes draw on the deposit
+        context.attached_deposit = 0;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        let account_id = PREDECESSOR_ACCOUNT_ADDRESS.to_string();
+        let available = |contract: &Contract| {
+            contract
+                .storage_balance_of(account_id.clone().try_into().unwrap())
+                .unwrap()
+                .available
+                .0
+        };
+        let registered = available(&contract);
+        register_user_and_company(&mut contract);
+        let feedback = contract.create_feedback(Feedback::mock());
+        let written = available(&contract);
+        assert!(written < registered, "writes should be charged");
+
+        contract.vote(feedback.id, Reaction::Like);
+        assert!(available(&contract) < written, "vote should be charged");
+        contract.unvote(feedback.id);
+        assert_eq!(available(&contract), written, "unvote should be refunded");
+    }
+
+    // Test the minimum balance covers a user and its first feedbacks
+    #[test]
+    fn test_min_storage_balance() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = Contract::default();
+        let (_, company) = register_user_and_company(&mut contract);
+
+        let account_id = "d".repeat(64);
+        context.predecessor_account_id = account_id.clone();
+        context.attached_deposit = contract.storage_balance_bounds().min.0;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.storage_deposit(None, Some(true));
+
+        context.attached_deposit = 0;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        let text = "a".repeat(storage::MIN_STORAGE_TEXT_LEN as usize);
+        let mut user = User::mock();
+        user.name = text.clone();
+        user.title = text.clone();
+        contract.create_user(user);
+        for _ in 0..storage::MIN_STORAGE_FEEDBACKS {
+            let mut feedback = Feedback::mock();
+            feedback.company_id = company.id;
+            feedback.content = text.clone();
+            contract.create_feedback(feedback);
+        }
+
+        // Accounts with a user cannot unregister
+        context.attached_deposit = 1;
+        testing_env!(context);
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.storage_unregister(None);
+        }))
+        .unwrap_err();
+    }
+
+    // Test storage freed by another account is refunded to the payer
+    #[test]
+    fn test_storage_refunded_to_payer() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        let (_, company) = register_user_and_company(&mut contract);
+        let locked = |contract: &Contract, account_id: &str| {
+            let balance = contract
+                .storage_balance_of(account_id.try_into().unwrap())
+                .unwrap();
+            balance.total.0 - balance.available.0
+        };
+
+        set_predecessor("dave_near");
+        contract.storage_deposit(None, None);
+        let registered = locked(&contract, "dave_near");
+        let claim = contract.submit_claim(
+            company.id,
+            "mock.example".to_string(),
+            "HRB 12345".to_string(),
+        );
+        assert!(
+            locked(&contract, "dave_near") > registered,
+            "claim should be charged to claimant"
+        );
+
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        let verifier = locked(&contract, PREDECESSOR_ACCOUNT_ADDRESS);
+        contract.resolve_claim(claim.id, ClaimDecision::Reject, "".to_string());
+        assert_eq!(
+            locked(&contract, "dave_near"),
+            registered,
+            "claimant should be refunded"
+        );
+        assert!(
+            locked(&contract, PREDECESSOR_ACCOUNT_ADDRESS) > verifier,
+            "decision should be charged to verifier"
+        );
+    }
+
+    // Test state changes emit NEP-297 events
+    #[test]
+    fn test_events() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+        let feedback = contract.create_feedback(Feedback::mock());
+        contract.vote(feedback.id, Reaction::Like);
+        contract.vote(feedback.id, Reaction::Dislike);
+
+        let events: Vec<serde_json::Value> = near_sdk::test_utils::get_logs()
+            .iter()
+            .map(|log| {
+                let json = log
+                    .strip_prefix("EVENT_JSON:")
+                    .expect("log is not an event");
+                serde_json::from_str(json).unwrap()
+            })
+            .collect();
+        let names: Vec<&str> = events
+            .iter()
+            .map(|event| event["event"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "user_created",
+                "company_created",
+                "company_activation_changed",
+                "feedback_created",
+                "feedback_voted",
+                "feedback_voted",
+            ]
+        );
+        for event in &events {
+            assert_eq!(event["standard"], "company_feedback");
+            assert_eq!(event["version"], "1.0.0");
+            assert_eq!(event["data"]["account_id"], PREDECESSOR_ACCOUNT_ADDRESS);
+        }
+        assert_eq!(events[3]["data"]["feedback"]["id"], feedback.id);
+        assert_eq!(events[5]["data"]["reaction"], "Dislike");
+        assert_eq!(events[5]["data"]["old_reaction"], "Like");
+    }
+
+    // Test fn create_user
+    #[test]
+    fn test_create_user() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        let mut mock_user = User::mock();
+        mock_user.account_id = "dave_near".to_string();
+        let first_user = contract.create_user(mock_user.clone());
+        assert_eq!(first_user.name, mock_user.name, "first_user is not equal");
+        assert_eq!(
+            first_user.account_id,
+            PREDECESSOR_ACCOUNT_ADDRESS.to_string(),
+            "first_user should be bound to the caller"
+        );
+    }
+
+    // Test fn create_user fail when account already has a user
+    #[test]
+    fn test_fail_create_user_twice() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        contract.create_user(User::mock());
+        catch_unwind_silent(move || {
+            contract.create_user(User::mock());
+        })
+        .unwrap_err();
+    }
+
+    // Test fn get_user_by_account
+    #[test]
+    fn test_get_user_by_account() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        assert!(
+            contract
+                .get_user_by_account(PREDECESSOR_ACCOUNT_ADDRESS.to_string())
+                .is_none(),
+            "user should not exist before create"
+        );
+
+        let first_user = contract.create_user(User::mock());
+        let found_user = contract
+            .get_user_by_account(PREDECESSOR_ACCOUNT_ADDRESS.to_string())
+            .unwrap();
+        assert_eq!(found_user.id, first_user.id, "found_user is not correct");
+    }
+
+    // Test fn update_user
+    #[test]
+    fn test_update_user() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        let mock_user = User::mock();
+        let first_user = contract.create_user(mock_user.clone());
+        assert_eq!(first_user.name, mock_user.name, "first_user is not equal");
+
+        let patch = UserPatch {
+            name: Some("user2".to_string()),
+            ..Default::default()
+        };
+
+        let first_user_updated = contract.update_user(first_user.id, patch);
+        assert_eq!(
+            first_user_updated.name, "user2",
+            "first_user_updated is not equal"
+        );
+        assert_eq!(
+            first_user_updated.title, first_user.title,
+            "first_user_updated title should not change"
+        );
+        assert_eq!(
+            first_user_updated.account_id, first_user.account_id,
+            "first_user_updated account should not change"
+        );
+    }
+
+    // Test fn update_user fail
+    #[test]
+    fn test_fail_update_user() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        catch_unwind_silent(move || {
+            contract.update_user(99999, UserPatch::default());
+        })
+        .unwrap_err();
+    }
+
+    // Test fn update_user fail when caller is not the bound account
+    #[test]
+    fn test_fail_update_user_not_account() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        set_predecessor("dave_near");
+        let first_user = contract.create_user(User::mock());
+
+        set_predecessor("eve_near");
+        catch_unwind_silent(move || {
+            contract.update_user(first_user.id, UserPatch::default());
+        })
+        .unwrap_err();
+    }
+
+    // Test fn update_active_user
+    #[test]
+    fn test_update_active_user() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        let mock_user = User::mock();
+        let first_user = contract.create_user(mock_user.clone());
+        assert!(first_user.activate, "user should be activate by default");
+
+        let updated_user = contract.update_active_user(first_user.id, false, None);
+        assert!(
+            !updated_user.activate,
+            "user should be deactivate after update"
+        );
+    }
+
+    // Test fn create_company
+    #[test]
+    fn test_create_company() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        let mock_company = Company::mock();
+        let first_company = contract.create_company(mock_company.clone());
+        assert_eq!(
+            first_company.name, mock_company.name,
+            "first_company is not equal"
+        );
+    }
+
+    // Test fn update_company
+    #[test]
+    fn test_update_company() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        let mock_company = Company::mock();
+        let first_company = contract.create_company(mock_company.clone());
+        assert_eq!(
+            first_company.name, mock_company.name,
+            "first_company is not equal"
+        );
+
+        let patch = CompanyPatch {
+            name: Some("company2".to_string()),
+            ..Default::default()
+        };
+
+        let first_company_updated = contract.update_company(first_company.id, patch);
+        assert_eq!(
+            first_company_updated.name, "company2",
+            "first_company_updated is not equal"
+        );
+        assert_eq!(
+            first_company_updated.rating, first_company.rating,
+            "rating should not change"
+        );
+        assert_eq!(
+            first_company_updated.activate, first_company.activate,
+            "activate should not change"
+        );
+    }
+
+    // Test fn update_company by manager
+    #[test]
+    fn test_update_company_by_manager() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        contract.grant_role("dave_near".to_string(), Role::CompanyVerifier);
+        set_predecessor("dave_near");
+        let first_company = contract.create_company(Company::mock());
+        assert_eq!(
+            first_company.managers,
+            vec!["dave_near".to_string()],
+            "verifier creator should be company manager"
+        );
+
+        let patch = CompanyPatch {
+            name: Some("company2".to_string()),
+            ..Default::default()
+        };
+        let first_company_updated = contract.update_company(first_company.id, patch);
+        assert_eq!(
+            first_company_updated.name, "company2",
+            "first_company_updated is not equal"
+        );
+        assert_eq!(
+            first_company_updated.managers, first_company.managers,
+            "managers should not change"
+        );
+    }
+
+    // Test fn update_company fail when caller is not a manager
+    #[test]
+    fn test_fail_update_company_not_manager() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        set_predecessor("dave_near");
+        let first_company = contract.create_company(Company::mock());
+
+        set_predecessor("eve_near");
+        catch_unwind_silent(move || {
+            contract.update_company(first_company.id, CompanyPatch::default());
+        })
+        .unwrap_err();
+    }
+
+    // Test fn update_company fail
+    #[test]
+    fn test_fail_update_company() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        catch_unwind_silent(move || {
+            contract.update_company(99999, CompanyPatch::default());
+        })
+        .unwrap_err();
+    }
+
+    // Test fn update_active company
+    #[test]
+    fn test_update_active_company() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        let mock_company = Company::mock();
+        let first_company = contract.create_company(mock_company.clone());
+        assert!(
+            !first_company.activate,
+            "company should be deactivate by default"
+        );
+
+        let updated_company = contract.update_active_company(first_company.id, true, None);
+        assert!(
+            updated_company.activate,
+            "company should be activate after update"
+        );
+    }
+
+    // Test fn migrate converts state written by the baseline layout
+    #[test]
+    fn test_migrate_baseline_state() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+
+        // Baseline layout, copied field for field from the first deployment
+        #[derive(BorshSerialize, BorshStorageKey)]
+        enum BaselineStorageKey {
+            Feedbacks,
+            Users,
+            Companies,
+        }
+        #[derive(BorshDeserialize, BorshSerialize)]
+        struct BaselineFeedback {
+            id: FeedbackId,
+            parent_id: FeedbackId,
+            user_id: UserId,
+            company_id: CompanyId,
+            content: String,
+            reaction: usize,
+            rating: usize,
+            up_vote: usize,
+            down_vote: usize,
+            report_vote: usize,
+            create_at: u64,
+            update_at: u64,
+            activate: bool,
+        }
+        #[derive(BorshDeserialize, BorshSerialize)]
+        struct BaselineUser {
+            id: UserId,
+            address: String,
+            name: String,
+            title: String,
+            create_at: u64,
+            update_at: u64,
+            activate: bool,
+        }
+        #[derive(BorshDeserialize, BorshSerialize)]
+        struct BaselineCompany {
+            id: CompanyId,
+            name: String,
+            rating: usize,
+            rating_count: usize,
+            company_type: String,
+            size: String,
+            address: String,
+            create_at: u64,
+            update_at: u64,
+            activate: bool,
+        }
+        #[derive(BorshDeserialize, BorshSerialize)]
+        struct BaselineContract {
+            owner_id: String,
+            feedbacks: UnorderedMap<FeedbackId, BaselineFeedback>,
+            users: UnorderedMap<UserId, BaselineUser>,
+            companies: UnorderedMap<CompanyId, BaselineCompany>,
+            next_feedback_id: FeedbackId,
+            next_user_id: UserId,
+            next_company_id: CompanyId,
+        }
+
+        // (parent_id, reaction, up_vote, rating) as stored by the old layout
+        let old_rows = [
+            (0, 0, 1, 4),
+            (0, 0, 1, 1_000_000),
+            (1, 1, 0, 3),
+            (1, 2, 0, 3),
+            (999, 4, 0, 0),
+            (1, 0, 0, 3),
+            (0, 0, 0, 0),
+        ];
+        let mut old_feedbacks = UnorderedMap::new(BaselineStorageKey::Feedbacks);
+        for (id, (parent_id, reaction, up_vote, rating)) in old_rows.iter().enumerate() {
+            let old_feedback = BaselineFeedback {
+                id,
+                parent_id: *parent_id,
+                user_id: 0,
+                company_id: 0,
+                content: "Mock feedback".to_string(),
+                reaction: *reaction,
+                rating: *rating,
+                up_vote: *up_vote,
+                down_vote: if id == 1 { 1 } else { 0 },
+                report_vote: if id == 1 { 1 } else { 0 },
+                create_at: 0,
+                update_at: 0,
+                activate: id != 4,
+            };
+            old_feedbacks.insert(&id, &old_feedback);
+        }
+        let mut old_users = UnorderedMap::new(BaselineStorageKey::Users);
+        for (id, address) in [PREDECESSOR_ACCOUNT_ADDRESS, "dave_near", "dave_near"]
+            .iter()
+            .enumerate()
+        {
+            let old_user = BaselineUser {
+                id,
+                address: address.to_string(),
+                name: format!("User {}", id),
+                title: "Mock Title".to_string(),
+                create_at: 0,
+                update_at: 0,
+                activate: true,
+            };
+            old_users.insert(&id, &old_user);
+        }
+        let mut old_companies = UnorderedMap::new(BaselineStorageKey::Companies);
+        let old_company = BaselineCompany {
+            id: 0,
+            name: "Mock Company".to_string(),
+            rating: 1_000_013,
+            rating_count: 6,
+            company_type: "Mock Company Type".to_string(),
+            size: "Mock Company Size".to_string(),
+            address: "Mock Company Address".to_string(),
+            create_at: 0,
+            update_at: 0,
+            activate: true,
+        };
+        old_companies.insert(&0, &old_company);
+        let old_contract = BaselineContract {
+            owner_id: PREDECESSOR_ACCOUNT_ADDRESS.to_string(),
+            feedbacks: old_feedbacks,
+            users: old_users,
+            companies: old_companies,
+            next_feedback_id: 7,
+            next_user_id: 3,
+            next_company_id: 1,
+        };
+        env::state_write(&old_contract);
 
-        let mock_user = User::mock();
-        let first_user = contract.create_user(mock_user.clone());
+        let mut contract = Contract::migrate();
+        assert_eq!(
+            contract.feedbacks.len(),
+            0,
+            "rows are left to migrate_batch"
+        );
+        assert!(!contract.migrate_batch(1));
+        assert!(contract.user(0).is_some());
+        assert!(contract.user(1).is_none());
 
-        mock_feedback.user_id = first_user.id;
+        // Rows can't be written until every row is converted
+        set_predecessor("dave_near");
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.create_user(User::mock());
+        }))
+        .unwrap_err();
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.migrate_batch(1);
+        }))
+        .unwrap_err();
 
-        let first_feedback = contract.create_feedback(mock_feedback.clone());
-        contract.update_active_feedback(first_feedback.id, true);
+        // Later batches resume where the last one stopped
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        while !contract.migrate_batch(4) {}
+        assert!(contract.migration.is_none());
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.migrate_batch(1);
+        }))
+        .unwrap_err();
+        assert_eq!(contract.feedbacks.len(), 7, "feedbacks should be kept");
+        assert_eq!(contract.next_feedback_id, 7);
+        let migrated: Vec<(Option<FeedbackId>, Reaction)> = (0..7)
+            .map(|id| {
+                let feedback = contract.get_feedback(id);
+                (feedback.parent_id, feedback.reaction)
+            })
+            .collect();
+        assert_eq!(
+            migrated,
+            vec![
+                (None, Reaction::None),
+                (None, Reaction::None),
+                (Some(1), Reaction::Dislike),
+                (Some(1), Reaction::Report),
+                (None, Reaction::None),
+                (Some(1), Reaction::Like),
+                (None, Reaction::None),
+            ],
+            "feedbacks are not migrated correctly"
+        );
 
+        let root_feedback = contract.get_feedback(0);
         assert_eq!(
-            first_feedback.user_id, first_user.id,
-            "first_feedback user_id is not correct"
+            root_feedback.up_vote, 0,
+            "root review votes should be dropped"
+        );
+        let parent_feedback = contract.get_feedback(1);
+        assert_eq!(parent_feedback.up_vote, 1, "up_vote is not correct");
+        assert_eq!(parent_feedback.down_vote, 1, "down_vote is not correct");
+        assert_eq!(parent_feedback.report_vote, 1, "report_vote is not correct");
+        assert_eq!(
+            parent_feedback.rating, 0,
+            "rating out of scale should be dropped"
         );
+        let replies: Vec<FeedbackId> = contract
+            .get_feedbacks_by_parent_id_paging(1, None, 10)
+            .items
+            .iter()
+            .map(|feedback| feedback.id)
+            .collect();
+        assert_eq!(replies, vec![5, 3, 2], "parent index is not built");
+        assert_eq!(contract.get_feedback(2).rating, 0, "reply has no rating");
 
-        let get_feedbacks = contract.get_feedbacks_by_user_id_paging(first_user.id, 0, 10);
+        let company = contract.get_company(0);
+        assert_eq!(company.rating_count, 1, "unrated reviews should not count");
+        assert_eq!(company.rating_histogram, vec![0, 0, 0, 1, 0]);
+        assert_eq!(contract.company(0).unwrap().rating, 4);
+        // Unrated reviews are still listed
+        assert_eq!(contract.get_feedback(6).rating, 0);
+        assert_eq!(
+            contract
+                .get_feedbacks_by_company_id_paging(0, None, 10, None)
+                .total,
+            6
+        );
 
-        assert_eq!(get_feedbacks.len(), 1, "get_feedbacks not found");
+        // Their author can edit them and rate them later, within scale only
+        let patch = || FeedbackPatch {
+            content: Some("Edited".to_string()),
+            rating: None,
+        };
+        contract.update_feedback(6, patch());
+        assert_eq!(contract.get_company(0).rating_count, 1);
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.update_feedback(
+                6,
+                FeedbackPatch {
+                    rating: Some(6),
+                    ..patch()
+                },
+            );
+        }))
+        .unwrap_err();
+        contract.update_feedback(
+            6,
+            FeedbackPatch {
+                rating: Some(2),
+                ..patch()
+            },
+        );
+        assert_eq!(contract.get_company(0).rating_count, 2);
+        assert!(
+            company.managers.is_empty(),
+            "company should have no manager"
+        );
+        let user = contract.get_user_by_account(PREDECESSOR_ACCOUNT_ADDRESS.to_string());
+        assert_eq!(user.unwrap().name, "User 0", "users should be kept");
+        let user = contract
+            .get_user_by_account("dave_near".to_string())
+            .unwrap();
+        assert_eq!(user.id, 1, "lowest user id should keep the account");
+        assert_eq!(contract.user(2).unwrap().account_id, "dave_near");
+        assert_eq!(
+            env::storage_read(b"STATE_VERSION"),
+            Some(vec![migration::STATE_VERSION]),
+            "state version is not recorded"
+        );
+    }
 
-        contract.create_feedback(mock_feedback.clone());
+    // Test fn migrate reads current state back unchanged
+    #[test]
+    fn test_migrate_current_state() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        let (user, company) = register_user_and_company(&mut contract);
+        let feedback = contract.create_feedback(Feedback::mock());
+        env::state_write(&contract);
 
+        let contract = Contract::migrate();
+        assert_eq!(contract.get_feedback(feedback.id).content, feedback.content);
+        assert_eq!(contract.get_company(company.id).rating_count, 1);
         assert_eq!(
-            get_feedbacks.len(),
-            1,
-            "get_feedbacks length is not correct"
+            contract.get_user_by_account(user.account_id).unwrap().id,
+            user.id
         );
+        assert_eq!(contract.get_feedbacks(None, 10, None).total, 1);
 
-        for feedback in get_feedbacks {
-            assert_eq!(
-                feedback.user_id, first_user.id,
-                "feedback user_id is not correct"
-            );
-        }
+        // State written by newer code is rejected
+        env::storage_write(b"STATE_VERSION", &[migration::STATE_VERSION + 1]);
+        catch_unwind_silent(|| {
+            Contract::migrate();
+        })
+        .unwrap_err();
     }
 
-    // Test fn get_feedbacks_by_parent_id_paging
+    // Test fn upgrade deploys code and chains migrate
     #[test]
-    fn test_get_feedbacks_by_parent_id_paging() {
+    fn test_upgrade() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
 
-        let mut mock_feedback = Feedback::mock();
-        mock_feedback.parent_id = 999;
+        contract.upgrade(vec![0, 97, 115, 109]);
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts.len(), 1, "upgrade should create one receipt");
+        let receipt = format!("{:?}", receipts[0]);
+        assert!(receipt.contains(&format!("receiver_id: {:?}", CURRENT_ACCOUNT_ADDRESS)));
+        assert!(receipt.contains("DeployContractAction { code: [0, 97, 115, 109] }"));
+        assert!(receipt.contains("method_name: [109, 105, 103, 114, 97, 116, 101]"));
 
-        let first_feedback = contract.create_feedback(mock_feedback.clone());
-        contract.update_active_feedback(first_feedback.id, true);
+        // Upgrade fails when gas can't cover itself
+        let mut context = get_context(vec![], false);
+        context.prepaid_gas = migration::GAS_FOR_UPGRADE - 1;
+        testing_env!(context);
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.upgrade(vec![0, 97, 115, 109]);
+        }))
+        .unwrap_err();
 
-        mock_feedback.parent_id = first_feedback.id;
-        let second_feedback = contract.create_feedback(mock_feedback.clone());
-        contract.update_active_feedback(second_feedback.id, true);
+        // Only owner may upgrade
+        set_predecessor("eve_near");
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.upgrade(vec![0, 97, 115, 109]);
+        }))
+        .unwrap_err();
+    }
+
+    // Test fn migrate fail when caller is not the owner
+    #[test]
+    fn test_fail_migrate_not_owner() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        env::state_write(&Contract::default());
 
-        let feedbacks = contract.get_feedbacks_by_parent_id_paging(first_feedback.id, 0, 10);
+        set_predecessor("eve_near");
+        catch_unwind_silent(|| {
+            Contract::migrate();
+        })
+        .unwrap_err();
+    }
 
-        assert_eq!(feedbacks.len(), 1, "feedbacks not found");
+    // Test fn propose_owner and fn accept_ownership
+    #[test]
+    fn test_transfer_ownership() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+
+        contract.propose_owner("dave_near".to_string());
+        assert_eq!(contract.get_pending_owner(), Some("dave_near".to_string()));
+        // Proposing is paid like any other write, registering the owner
+        assert!(contract
+            .storage_balance_of(PREDECESSOR_ACCOUNT_ADDRESS.try_into().unwrap())
+            .is_some());
+        // Owner keeps control until the proposal is accepted
         assert_eq!(
-            feedbacks[0].parent_id, first_feedback.id,
-            "feedback id is not correct"
+            contract.get_owner(),
+            Some(PREDECESSOR_ACCOUNT_ADDRESS.to_string())
         );
 
-        let third_feedback = contract.create_feedback(mock_feedback.clone());
-        contract.update_active_feedback(third_feedback.id, true);
-        let feedbacks = contract.get_feedbacks_by_parent_id_paging(first_feedback.id, 0, 10);
+        set_predecessor("eve_near");
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.accept_ownership();
+        }))
+        .unwrap_err();
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.propose_owner("eve_near".to_string());
+        }))
+        .unwrap_err();
 
-        assert_eq!(feedbacks.len(), 2, "feedbacks not found");
+        set_predecessor("dave_near");
+        contract.accept_ownership();
+        assert_eq!(contract.get_owner(), Some("dave_near".to_string()));
+        assert_eq!(contract.get_pending_owner(), None);
+        let logs = near_sdk::test_utils::get_logs();
+        let event: serde_json::Value =
+            serde_json::from_str(logs[0].strip_prefix("EVENT_JSON:").unwrap()).unwrap();
+        assert_eq!(event["event"], "ownership_transferred");
+        assert_eq!(event["data"]["old_owner_id"], PREDECESSOR_ACCOUNT_ADDRESS);
+        assert_eq!(event["data"]["new_owner_id"], "dave_near");
+
+        // Previous owner lost owner-only methods
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.propose_owner(PREDECESSOR_ACCOUNT_ADDRESS.to_string());
+        }))
+        .unwrap_err();
+    }
+
+    // Test fn renounce_ownership
+    #[test]
+    fn test_renounce_ownership() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        contract.propose_owner("dave_near".to_string());
+
+        contract.renounce_ownership();
+        assert_eq!(contract.get_owner(), None);
+        assert_eq!(contract.get_pending_owner(), None);
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.set_rating_scale(1, 5);
+        }))
+        .unwrap_err();
+
+        // A pending proposal can't be accepted after renouncing
+        set_predecessor("dave_near");
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.accept_ownership();
+        }))
+        .unwrap_err();
+    }
+
+    // Test fn grant_role and fn revoke_role
+    #[test]
+    fn test_roles() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        let (_, company) = register_user_and_company(&mut contract);
+        let feedback = contract.create_feedback(Feedback::mock());
+        let owner = PREDECESSOR_ACCOUNT_ADDRESS.to_string();
+        assert!(contract.has_role(owner.clone(), Role::Owner));
+        assert!(contract.has_role(owner, Role::Moderator));
+
+        contract.grant_role("dave_near".to_string(), Role::Admin);
+        assert!(contract.has_role("dave_near".to_string(), Role::CompanyVerifier));
+        assert!(!contract.has_role("dave_near".to_string(), Role::Owner));
+        // Owner role only moves through ownership transfer
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.grant_role("dave_near".to_string(), Role::Owner);
+        }))
+        .unwrap_err();
+
+        // Admins grant moderators but not other admins
+        set_predecessor("dave_near");
+        contract.grant_role("eve_near".to_string(), Role::Moderator);
         assert_eq!(
-            feedbacks[1].parent_id, first_feedback.id,
-            "third_feedback parent_id is not correct"
+            contract.get_roles("eve_near".to_string()),
+            vec![Role::Moderator]
+        );
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.grant_role("eve_near".to_string(), Role::Admin);
+        }))
+        .unwrap_err();
+
+        // Moderators moderate feedbacks but not companies or roles
+        set_predecessor("eve_near");
+        assert!(
+            !contract
+                .update_active_feedback(feedback.id, false, None)
+                .activate
         );
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.update_active_company(company.id, false, None);
+        }))
+        .unwrap_err();
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.grant_role("bob_near".to_string(), Role::Moderator);
+        }))
+        .unwrap_err();
 
-        for feedback in feedbacks {
-            assert_eq!(
-                feedback.parent_id, first_feedback.id,
-                "feedback parent_id is not correct"
-            );
+        set_predecessor("dave_near");
+        contract.revoke_role("eve_near".to_string(), Role::Moderator);
+        assert!(contract.get_roles("eve_near".to_string()).is_empty());
+        set_predecessor("eve_near");
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.update_active_feedback(feedback.id, true, None);
+        }))
+        .unwrap_err();
+    }
+
+    // Test moderation queue pages in queue order and drops reactivated feedback
+    #[test]
+    fn test_moderation_queue_paging() {
+        let mut context = get_context(vec![], false);
+        testing_env!(context.clone());
+        let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+        for _ in 0..3 {
+            contract.create_feedback(Feedback::mock());
+        }
+        contract.set_report_threshold(1);
+
+        context.predecessor_account_id = "dave_near".to_string();
+        for (queue_at, id) in [(3, 2), (1, 0), (2, 1)] {
+            context.block_timestamp = queue_at;
+            context.storage_usage = env::storage_usage();
+            testing_env!(context.clone());
+            contract.vote(id, Reaction::Report);
         }
+
+        let ids = |page: &Page<Feedback>| -> Vec<FeedbackId> {
+            page.items.iter().map(|feedback| feedback.id).collect()
+        };
+        let page = contract.get_moderation_queue(None, 2);
+        assert_eq!(ids(&page), vec![0, 1]);
+        assert_eq!(page.total, 3);
+        let page = contract.get_moderation_queue(page.next_cursor, 2);
+        assert_eq!(ids(&page), vec![2]);
+        assert!(page.next_cursor.is_none());
+
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        contract.update_active_feedback(1, true, None);
+        let page = contract.get_moderation_queue(None, 10);
+        assert_eq!(ids(&page), vec![0, 2]);
+        assert_eq!(page.total, 2);
     }
 
-    // Test fn get_feedbacks_by_company_id_paging
+    // Test reported feedback is hidden and fn resolve_report
     #[test]
-    fn test_get_feedbacks_by_company_id_paging() {
+    fn test_moderation_queue() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        let (_, company) = register_user_and_company(&mut contract);
+        let feedback = contract.create_feedback(Feedback::mock());
+        contract.set_report_threshold(2);
 
-        // Create company
-        let mock_company = Company::mock();
-        let first_company = contract.create_company(mock_company.clone());
+        set_predecessor("dave_near");
+        contract.vote(feedback.id, Reaction::Report);
+        assert!(contract.get_moderation_queue(None, 10).items.is_empty());
+        set_predecessor("eve_near");
+        let reported = contract.vote(feedback.id, Reaction::Report);
+        assert!(!reported.activate, "reported feedback should be hidden");
+        assert_eq!(contract.get_company(company.id).rating_count, 0);
+        let queue = contract.get_moderation_queue(None, 10);
+        assert_eq!(queue.total, 1);
+        assert_eq!(queue.items[0].id, feedback.id);
 
-        // Create feedback by company
-        let mut mock_feedback = Feedback::mock();
-        mock_feedback.company_id = first_company.id;
-        let first_feedback = contract.create_feedback(mock_feedback.clone());
-        contract.update_active_feedback(first_feedback.id, true);
+        // Only moderators resolve reports
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.resolve_report(feedback.id, ReportDecision::Restore, "Fine".to_string());
+        }))
+        .unwrap_err();
 
-        // Verify feedback by company id
-        let feedbacks = contract.get_feedbacks_by_company_id_paging(first_company.id, 0, 10);
-        assert_eq!(feedbacks.len(), 1, "feedbacks not found");
-        assert_eq!(
-            feedbacks[0].company_id, first_company.id,
-            "feedback company_id is not correct"
-        );
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        let restored =
+            contract.resolve_report(feedback.id, ReportDecision::Restore, "Fine".to_string());
+        assert!(restored.activate);
+        assert_eq!(contract.get_company(company.id).rating_count, 1);
+        assert_eq!(contract.get_moderation_queue(None, 10).total, 0);
+        let resolution = contract.get_report_resolution(feedback.id).unwrap();
+        assert_eq!(resolution.decision, ReportDecision::Restore);
+        assert_eq!(resolution.moderator_id, PREDECESSOR_ACCOUNT_ADDRESS);
+        assert_eq!(resolution.report_vote, 2);
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.resolve_report(feedback.id, ReportDecision::Remove, "Again".to_string());
+        }))
+        .unwrap_err();
 
-        // Create feedback by company
-        let second_feedback = contract.create_feedback(mock_feedback.clone());
-        contract.update_active_feedback(second_feedback.id, true);
+        // Only reports since the decision count toward hiding again
+        set_predecessor("grace_near");
+        assert!(contract.vote(feedback.id, Reaction::Report).activate);
+        set_predecessor("heidi_near");
+        assert!(!contract.vote(feedback.id, Reaction::Report).activate);
 
-        // Verify feedback by company id
-        let feedbacks = contract.get_feedbacks_by_company_id_paging(first_company.id, 0, 10);
-        assert_eq!(feedbacks.len(), 2, "feedbacks not found");
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        let removed =
+            contract.resolve_report(feedback.id, ReportDecision::Remove, "Spam".to_string());
+        assert!(!removed.activate);
+        assert_eq!(contract.get_company(company.id).rating_count, 0);
         assert_eq!(
-            feedbacks[1].company_id, first_company.id,
-            "feedback company_id is not correct"
+            contract.get_report_resolution(feedback.id).unwrap().reason,
+            "Spam"
         );
     }
 
-    // Test fn create_user
+    // Test fn update_active_feedback resolves the report of queued feedback
     #[test]
-    fn test_create_user() {
+    fn test_update_active_feedback_resolves_report() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+        let feedback = contract.create_feedback(Feedback::mock());
+        contract.set_report_threshold(2);
+        for reporter in ["dave_near", "eve_near"].iter() {
+            set_predecessor(reporter);
+            contract.vote(feedback.id, Reaction::Report);
+        }
+        assert_eq!(contract.get_moderation_queue(None, 10).total, 1);
 
-        let mock_user = User::mock();
-        let first_user = contract.create_user(mock_user.clone());
-        assert_eq!(first_user.name, mock_user.name, "first_user is not equal");
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        contract.update_active_feedback(feedback.id, true, Some("Fine".to_string()));
+        assert_eq!(contract.get_moderation_queue(None, 10).total, 0);
+        let resolution = contract.get_report_resolution(feedback.id).unwrap();
+        assert_eq!(resolution.decision, ReportDecision::Restore);
+        assert_eq!(resolution.reason, "Fine");
+        assert_eq!(resolution.report_vote, 2);
+
+        // Reports made before the reactivation don't hide it again
+        set_predecessor("grace_near");
+        assert!(contract.vote(feedback.id, Reaction::Report).activate);
+        set_predecessor("heidi_near");
+        assert!(!contract.vote(feedback.id, Reaction::Report).activate);
     }
 
-    // Test fn update_user
+    // Test fn get_moderation_log records who moderated what and why
     #[test]
-    fn test_update_user() {
+    fn test_moderation_log() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        let (user, company) = register_user_and_company(&mut contract);
+        let feedback = contract.create_feedback(Feedback::mock());
+        contract.set_report_threshold(1);
+        contract.update_active_user(user.id, false, Some("Spam account".to_string()));
+        contract.update_active_user(user.id, true, None);
 
-        let mock_user = User::mock();
-        let first_user = contract.create_user(mock_user.clone());
-        assert_eq!(first_user.name, mock_user.name, "first_user is not equal");
+        set_predecessor("dave_near");
+        contract.vote(feedback.id, Reaction::Report);
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        contract.resolve_report(feedback.id, ReportDecision::Remove, "Abuse".to_string());
 
-        let mut mock_user_2 = User::mock();
-        mock_user_2.name = "user2".to_string();
+        let log = contract.get_moderation_log(None, None, 10);
+        assert_eq!(log.total, 5);
+        let actions: Vec<ModerationAction> = log.items.iter().map(|entry| entry.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                ModerationAction::Remove,
+                ModerationAction::Hide,
+                ModerationAction::Activate,
+                ModerationAction::Deactivate,
+                ModerationAction::Activate,
+            ]
+        );
+        assert_eq!(log.items[1].actor_id, CURRENT_ACCOUNT_ADDRESS);
+        assert_eq!(log.items[4].target, ModerationTarget::Company(company.id));
+        let first_page = contract.get_moderation_log(None, None, 2);
+        assert_eq!(first_page.items[0].action, ModerationAction::Remove);
+        let second_page = contract.get_moderation_log(None, first_page.next_cursor, 2);
+        assert_eq!(second_page.items[0].action, ModerationAction::Activate);
+        assert_eq!(second_page.total, 5);
+        let last_page = contract.get_moderation_log(None, second_page.next_cursor, 2);
+        assert_eq!(last_page.items.len(), 1);
+        assert!(last_page.next_cursor.is_none());
 
-        let first_user_updated = contract.update_user(first_user.id, mock_user_2.clone());
+        // Filter by target and page
+        let target = ModerationTarget::User(user.id);
+        let first_page = contract.get_moderation_log(Some(target), None, 1);
+        assert_eq!(first_page.total, 2);
+        assert_eq!(first_page.items[0].action, ModerationAction::Activate);
+        let second_page = contract.get_moderation_log(Some(target), first_page.next_cursor, 1);
+        assert_eq!(second_page.items[0].actor_id, PREDECESSOR_ACCOUNT_ADDRESS);
         assert_eq!(
-            first_user_updated.name, mock_user_2.name,
-            "first_user_updated is not equal"
+            second_page.items[0].reason,
+            Some("Spam account".to_string())
         );
+        assert!(second_page.next_cursor.is_none());
+
+        let feedback_log =
+            contract.get_moderation_log(Some(ModerationTarget::Feedback(feedback.id)), None, 10);
+        assert_eq!(feedback_log.items[0].reason, Some("Abuse".to_string()));
     }
 
-    // Test fn update_user fail
+    // Test fn file_appeal and fn resolve_appeal
     #[test]
-    fn test_fail_update_user() {
+    fn test_appeals() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+        let feedback = contract.create_feedback(Feedback::mock());
+        let message = "Review is accurate".to_string();
 
-        catch_unwind_silent(move || {
-            contract.update_user(99999, User::mock().clone());
-        })
+        // Active feedback can't be appealed
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.file_appeal(feedback.id, message.clone());
+        }))
+        .unwrap_err();
+        contract.update_active_feedback(feedback.id, false, Some("Offensive".to_string()));
+
+        // Only the author appeals
+        set_predecessor("dave_near");
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.file_appeal(feedback.id, message.clone());
+        }))
+        .unwrap_err();
+
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        let appeal = contract.file_appeal(feedback.id, message.clone());
+        assert_eq!(appeal.decision, None);
+        let open_appeals = contract.get_open_appeals(None, 10);
+        assert_eq!(open_appeals.total, 1);
+        assert_eq!(open_appeals.items[0].message, message);
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.file_appeal(feedback.id, message.clone());
+        }))
+        .unwrap_err();
+
+        let appeal = contract.resolve_appeal(
+            feedback.id,
+            AppealDecision::Reinstate,
+            "Not offensive".to_string(),
+        );
+        assert_eq!(appeal.decision, Some(AppealDecision::Reinstate));
+        assert_eq!(
+            appeal.moderator_id,
+            Some(PREDECESSOR_ACCOUNT_ADDRESS.to_string())
+        );
+        assert!(contract.get_feedback(feedback.id).activate);
+        assert_eq!(contract.get_open_appeals(None, 10).total, 0);
+        let log = contract.get_moderation_log(None, None, 1);
+        assert_eq!(log.items[0].action, ModerationAction::Reinstate);
+
+        // Reinstated feedback deactivated again can be appealed again, closing is final
+        contract.update_active_feedback(feedback.id, false, None);
+        contract.file_appeal(feedback.id, message.clone());
+        contract.resolve_appeal(feedback.id, AppealDecision::Close, "Offensive".to_string());
+        assert!(!contract.get_feedback(feedback.id).activate);
+        assert_eq!(
+            contract.get_appeal(feedback.id).unwrap().decision,
+            Some(AppealDecision::Close)
+        );
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.file_appeal(feedback.id, message.clone());
+        }))
+        .unwrap_err();
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.resolve_appeal(feedback.id, AppealDecision::Reinstate, "".to_string());
+        }))
         .unwrap_err();
     }
 
-    // Test fn update_active_user
+    // Test fn update_active_feedback decides the open appeal of feedback
     #[test]
-    fn test_update_active_user() {
+    fn test_update_active_feedback_reinstates_appeal() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        register_user_and_company(&mut contract);
+        let feedback = contract.create_feedback(Feedback::mock());
+        contract.update_active_feedback(feedback.id, false, None);
+        contract.file_appeal(feedback.id, "Review is accurate".to_string());
 
-        let mock_user = User::mock();
-        let first_user = contract.create_user(mock_user.clone());
+        contract.update_active_feedback(feedback.id, true, Some("Accurate".to_string()));
+        assert_eq!(contract.get_open_appeals(None, 10).total, 0);
+        let appeal = contract.get_appeal(feedback.id).unwrap();
+        assert_eq!(appeal.decision, Some(AppealDecision::Reinstate));
+        assert_eq!(appeal.reason, Some("Accurate".to_string()));
+
+        // The stale appeal can't be closed on active feedback
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.resolve_appeal(feedback.id, AppealDecision::Close, "Stale".to_string());
+        }))
+        .unwrap_err();
+        assert!(contract.get_feedback(feedback.id).activate);
+    }
+
+    // Test fn submit_claim and fn resolve_claim
+    #[test]
+    fn test_company_claims() {
+        let context = get_context(vec![], false);
+        testing_env!(context);
+        let mut contract = Contract::default();
+        let (_, company) = register_user_and_company(&mut contract);
+        let domain = "mock.example".to_string();
+        let registry_number = "HRB 12345".to_string();
+
+        // Managers can't claim their own company
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.submit_claim(company.id, domain.clone(), registry_number.clone());
+        }))
+        .unwrap_err();
+
+        set_predecessor("dave_near");
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.submit_claim(company.id, domain.clone(), "".to_string());
+        }))
+        .unwrap_err();
+        let claim = contract.submit_claim(company.id, domain.clone(), registry_number.clone());
+        assert_eq!(claim.decision, None);
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.submit_claim(company.id, domain.clone(), registry_number.clone());
+        }))
+        .unwrap_err();
+        // Claimants can't decide their own claim
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.resolve_claim(claim.id, ClaimDecision::Approve, "".to_string());
+        }))
+        .unwrap_err();
+
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        let pending_claims = contract.get_pending_claims(None, 10);
+        assert_eq!(pending_claims.total, 1);
+        assert_eq!(pending_claims.items[0].registry_number, registry_number);
+        let rejected = contract.resolve_claim(
+            claim.id,
+            ClaimDecision::Reject,
+            "Domain not verified".to_string(),
+        );
+        assert_eq!(rejected.decision, Some(ClaimDecision::Reject));
+        assert_eq!(contract.get_company(company.id).managers.len(), 1);
+
+        // Rejected claims can be submitted again
+        set_predecessor("dave_near");
+        let claim = contract.submit_claim(company.id, domain.clone(), registry_number.clone());
         assert_eq!(
-            first_user.activate, false,
-            "user should be deactivate by default"
+            contract
+                .get_company_claim(company.id, "dave_near".to_string())
+                .unwrap()
+                .id,
+            claim.id
         );
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        contract.grant_role("eve_near".to_string(), Role::CompanyVerifier);
+        set_predecessor("eve_near");
+        let approved =
+            contract.resolve_claim(claim.id, ClaimDecision::Approve, "Verified".to_string());
+        assert_eq!(approved.verifier_id, Some("eve_near".to_string()));
+        assert!(contract
+            .get_company(company.id)
+            .managers
+            .contains(&"dave_near".to_string()));
+        assert_eq!(contract.get_pending_claims(None, 10).total, 0);
+        let log = contract.get_moderation_log(Some(ModerationTarget::Company(company.id)), None, 1);
+        assert_eq!(log.items[0].action, ModerationAction::ApproveClaim);
 
-        let updated_user = contract.update_active_user(first_user.id, true);
+        // Approved claimant manages the company
+        set_predecessor("dave_near");
+        let patch = CompanyPatch {
+            address: Some("New address".to_string()),
+            ..Default::default()
+        };
         assert_eq!(
-            updated_user.activate, true,
-            "user should be activate after update"
+            contract.update_company(company.id, patch).address,
+            "New address"
         );
     }
 
-    // Test fn create_company
+    // Test company creators manage it only once their claim is approved
     #[test]
-    fn test_create_company() {
+    fn test_fail_manage_unclaimed_company() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
 
-        let mock_company = Company::mock();
-        let first_company = contract.create_company(mock_company.clone());
-        assert_eq!(
-            first_company.name, mock_company.name,
-            "first_company is not equal"
+        set_predecessor("dave_near");
+        let company = contract.create_company(Company::mock());
+        assert!(company.managers.is_empty(), "creator should not be manager");
+
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        contract.update_active_company(company.id, true, None);
+        contract.create_user(User::mock());
+        let mut mock_feedback = Feedback::mock();
+        mock_feedback.company_id = company.id;
+        let feedback = contract.create_feedback(mock_feedback);
+
+        set_predecessor("dave_near");
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.update_company(company.id, CompanyPatch::default());
+        }))
+        .unwrap_err();
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.post_official_response(feedback.id, "Thanks".to_string());
+        }))
+        .unwrap_err();
+
+        let claim = contract.submit_claim(
+            company.id,
+            "mock.example".to_string(),
+            "HRB 12345".to_string(),
         );
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        contract.resolve_claim(claim.id, ClaimDecision::Approve, "Verified".to_string());
+        set_predecessor("dave_near");
+        let response = contract.post_official_response(feedback.id, "Thanks".to_string());
+        assert_eq!(response.account_id, "dave_near");
     }
 
-    // Test fn update_company
+    // Test fn post_official_response and fn update_official_response
     #[test]
-    fn test_update_company() {
+    fn test_official_response() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        let (_, company) = register_user_and_company(&mut contract);
+        let feedback = contract.create_feedback(Feedback::mock());
+        let mut mock_reply = Feedback::mock();
+        mock_reply.parent_id = Some(feedback.id);
+        mock_reply.rating = 0;
+        let reply = contract.create_feedback(mock_reply);
+        assert_eq!(contract.get_official_response(feedback.id), None);
 
-        let mock_company = Company::mock();
-        let first_company = contract.create_company(mock_company.clone());
+        // Replies don't get official responses
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.post_official_response(reply.id, "Thanks".to_string());
+        }))
+        .unwrap_err();
+
+        let response = contract.post_official_response(feedback.id, "Thanks".to_string());
+        assert_eq!(response.company_id, company.id);
+        assert_eq!(response.account_id, PREDECESSOR_ACCOUNT_ADDRESS);
         assert_eq!(
-            first_company.name, mock_company.name,
-            "first_company is not equal"
+            contract.get_official_response(feedback.id),
+            Some(response.clone())
         );
+        // Paged reviews carry their official response
+        let page = contract.get_root_feedbacks_by_company(company.id, None, 10, None);
+        assert_eq!(page.items[0].official_response, Some(response));
+        let page = contract.get_feedbacks_by_parent_id_paging(feedback.id, None, 10);
+        assert_eq!(page.items[0].official_response, None);
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.post_official_response(feedback.id, "Thanks again".to_string());
+        }))
+        .unwrap_err();
 
-        let mut mock_company_2 = Company::mock();
-        mock_company_2.name = "company2".to_string();
+        // Only company managers respond
+        set_predecessor("dave_near");
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.update_official_response(feedback.id, "Not us".to_string());
+        }))
+        .unwrap_err();
+        let claim = contract.submit_claim(
+            company.id,
+            "mock.example".to_string(),
+            "HRB 12345".to_string(),
+        );
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        contract.resolve_claim(claim.id, ClaimDecision::Approve, "Verified".to_string());
 
-        let first_company_updated =
-            contract.update_company(first_company.id, mock_company_2.clone());
+        set_predecessor("dave_near");
+        let updated = contract.update_official_response(feedback.id, "Fixed now".to_string());
+        assert_eq!(updated.account_id, "dave_near");
         assert_eq!(
-            first_company_updated.name, mock_company_2.name,
-            "first_company_updated is not equal"
+            contract.get_official_response(feedback.id).unwrap().content,
+            "Fixed now"
         );
     }
 
-    // Test fn update_company fail
+    // Test fn merge_batch moves the feedbacks a merge left on the source
     #[test]
-    fn test_fail_update_company() {
+    fn test_merge_batch() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        let (_, source) = register_user_and_company(&mut contract);
+        let mut mock_company = Company::mock();
+        mock_company.name = "Other Company".to_string();
+        let target = contract.create_company(mock_company);
+        contract.update_active_company(target.id, true, None);
+        let feedback_count = merge::MAX_MERGE_FEEDBACKS + 2;
+        for _ in 0..feedback_count {
+            // A fresh context per call keeps the event log under its limit
+            set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+            contract.create_feedback(Feedback::mock());
+        }
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.merge_batch(source.id, 10);
+        }))
+        .unwrap_err();
 
-        catch_unwind_silent(move || {
-            contract.update_company(99999, Company::mock().clone());
-        })
+        let merged = contract.merge_companies(source.id, target.id);
+        assert_eq!(merged.rating_count, merge::MAX_MERGE_FEEDBACKS);
+        let page = contract.get_feedbacks_by_company_id_paging(target.id, None, 1, None);
+        assert_eq!(page.total, merge::MAX_MERGE_FEEDBACKS);
+        assert_eq!(page.items[0].id, feedback_count - 1, "newest move first");
+
+        // Only admins move the rest
+        set_predecessor("dave_near");
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.merge_batch(source.id, 10);
+        }))
         .unwrap_err();
+
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        assert!(!contract.merge_batch(source.id, 1));
+        assert!(contract.merge_batch(source.id, 10));
+        assert!(contract.merge_batch(source.id, 10));
+        let target_row = contract.company(target.id).unwrap();
+        assert_eq!(target_row.rating_count, feedback_count);
+        let source_row = contract.company(source.id).unwrap();
+        assert_eq!((source_row.rating, source_row.rating_count), (0, 0));
+        let ids: Vec<FeedbackId> = contract
+            .get_feedbacks_by_company_id_paging(target.id, None, feedback_count, None)
+            .items
+            .iter()
+            .map(|feedback| feedback.id)
+            .collect();
+        assert_eq!(ids, (0..feedback_count).rev().collect::<Vec<FeedbackId>>());
     }
 
-    // Test fn update_active company
+    // Test company name uniqueness and fn merge_companies
     #[test]
-    fn test_update_active_company() {
+    fn test_merge_companies() {
         let context = get_context(vec![], false);
         testing_env!(context);
         let mut contract = Contract::default();
+        let (_, target) = register_user_and_company(&mut contract);
+        let target_feedback = contract.create_feedback(Feedback::mock());
 
-        let mock_company = Company::mock();
-        let first_company = contract.create_company(mock_company.clone());
+        // Names differing only in case and punctuation are duplicates
+        let mut mock_company = Company::mock();
+        mock_company.name = " mock  COMPANY!".to_string();
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.create_company(mock_company.clone());
+        }))
+        .unwrap_err();
+
+        mock_company.name = "Mock Company Ltd".to_string();
+        let source = contract.create_company(mock_company.clone());
+        contract.update_active_company(source.id, true, None);
+        set_predecessor("eve_near");
+        let claim = contract.submit_claim(
+            source.id,
+            "ltd.example".to_string(),
+            "HRB 54321".to_string(),
+        );
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        let mut mock_feedback = Feedback::mock();
+        mock_feedback.company_id = source.id;
+        mock_feedback.rating = 3;
+        let source_feedback = contract.create_feedback(mock_feedback);
+        contract.post_official_response(source_feedback.id, "Thanks".to_string());
+        let mut mock_feedback = Feedback::mock();
+        mock_feedback.company_id = target.id;
+        mock_feedback.rating = 4;
+        let newer_feedback = contract.create_feedback(mock_feedback);
         assert_eq!(
-            first_company.activate, false,
-            "company should be deactivate by default"
+            contract
+                .find_company_by_name("mock company, ltd.".to_string())
+                .unwrap()
+                .id,
+            source.id
         );
 
-        let updated_company = contract.update_active_company(first_company.id, true);
+        set_predecessor("dave_near");
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.merge_companies(source.id, target.id);
+        }))
+        .unwrap_err();
+
+        set_predecessor(PREDECESSOR_ACCOUNT_ADDRESS);
+        // Feedbacks can't be moved to a company that is not listed
+        contract.update_active_company(target.id, false, None);
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.merge_companies(source.id, target.id);
+        }))
+        .unwrap_err();
+        contract.update_active_company(target.id, true, None);
+
+        let merged = contract.merge_companies(source.id, target.id);
+        assert_eq!(merged.id, target.id);
+        let target_row = contract.company(target.id).unwrap();
+        assert_eq!((target_row.rating, target_row.rating_count), (12, 3));
+        assert_eq!(contract.get_company_redirect(source.id), Some(target.id));
+        assert_eq!(contract.get_company(source.id).id, target.id);
         assert_eq!(
-            updated_company.activate, true,
-            "company should be activate after update"
+            contract.get_feedback(source_feedback.id).company_id,
+            target.id
+        );
+        let response = contract.get_official_response(source_feedback.id).unwrap();
+        assert_eq!(response.company_id, target.id);
+        assert_eq!(response.account_id, PREDECESSOR_ACCOUNT_ADDRESS);
+
+        // A claim pending on the source can't make its claimant a manager
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.resolve_claim(claim.id, ClaimDecision::Approve, "".to_string());
+        }))
+        .unwrap_err();
+        contract.resolve_claim(claim.id, ClaimDecision::Reject, "Merged".to_string());
+        assert!(!contract
+            .get_company(target.id)
+            .managers
+            .contains(&"eve_near".to_string()));
+
+        // Merged listings stay newest first and redirect from the source
+        let page = contract.get_feedbacks_by_company_id_paging(source.id, None, 10, None);
+        assert_eq!(page.total, 3);
+        let ids: Vec<FeedbackId> = page.items.iter().map(|feedback| feedback.id).collect();
+        assert_eq!(
+            ids,
+            vec![newer_feedback.id, source_feedback.id, target_feedback.id]
+        );
+        assert_eq!(
+            contract
+                .get_root_feedbacks_by_company(target.id, None, 10, None)
+                .total,
+            3
+        );
+
+        // Source name resolves to the target and can't be registered again
+        assert_eq!(
+            contract
+                .find_company_by_name("Mock Company Ltd".to_string())
+                .unwrap()
+                .id,
+            target.id
+        );
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.create_company(mock_company.clone());
+        }))
+        .unwrap_err();
+        catch_unwind_silent(panic::AssertUnwindSafe(|| {
+            contract.update_active_company(source.id, true, None);
+        }))
+        .unwrap_err();
+
+        // Renaming moves the company in the index
+        let patch = CompanyPatch {
+            name: Some("Acme".to_string()),
+            ..Default::default()
+        };
+        contract.update_company(target.id, patch);
+        assert!(contract
+            .find_company_by_name("Mock Company".to_string())
+            .is_none());
+        assert_eq!(
+            contract
+                .find_company_by_name("ACME".to_string())
+                .unwrap()
+                .id,
+            target.id
         );
     }
 }
@@ -0,0 +1,280 @@
+/*!
+Duplicate company detection and merging.
+NOTES:
+  - Company names are unique after normalizing, which lowercases the name and
+    keeps only its letters and digits, one space between words.
+  - Admins merge a duplicate source company into an active target: feedbacks and
+    official responses move to the target, ratings and counts are recounted,
+    and the source is deactivated with a redirect to the target.
+  - Reads by company id follow redirects, writes to a merged company panic.
+  - The source name stays in the index pointing to the target, so the
+    duplicate can't be registered again.
+  - Managers of the source are not made managers of the target, they can
+    claim it.
+  - Official responses move with their review and keep the source manager
+    that wrote them as their account, until a target manager edits them.
+  - Merging moves the newest MAX_MERGE_FEEDBACKS feedbacks of the source,
+    admins move the rest with merge_batch so no call needs more gas than a
+    bounded batch. Until then they are listed nowhere, since the source
+    redirects, and count toward no rating of the target.
+*/
+use crate::*;
+use events::MergeData;
+
+/// Feedbacks moved by merge_companies, the rest is moved by merge_batch
+pub const MAX_MERGE_FEEDBACKS: usize = 20;
+
+/// Merge ascending source ids into ascending ids of a company index, only
+/// rewriting the entries from the first id after the oldest source id
+fn merge_feedback_ids(ids: &mut Vector<FeedbackId>, source_ids: &[FeedbackId]) {
+    let first_source_id = match source_ids.first() {
+        Some(id) => *id,
+        None => return,
+    };
+    let (mut start, mut high) = (0, ids.len());
+    while start < high {
+        let middle = (start + high) / 2;
+        if ids.get(middle).unwrap() < first_source_id {
+            start = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+
+    let mut tail: Vec<FeedbackId> = (start..ids.len())
+        .filter_map(|index| ids.get(index))
+        .chain(source_ids.iter().copied())
+        .collect();
+    tail.sort_unstable();
+    let len = ids.len();
+    for (index, id) in (start..).zip(tail.iter()) {
+        if index < len {
+            ids.replace(index, id);
+        } else {
+            ids.push(id);
+        }
+    }
+}
+
+/// Normalize company name for the uniqueness index
+pub fn normalize_company_name(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Find company by name, ignoring case and punctuation
+    /// @param String name
+    /// @return company
+    pub fn find_company_by_name(&self, name: String) -> Option<CompanyView> {
+        self.company_ids_by_name
+            .get(&normalize_company_name(&name))
+            .and_then(|id| self.company(self.resolve_company_id(id)))
+            .map(|company| self.company_view(company))
+    }
+
+    /// Get company a merged company redirects to
+    /// @param CompanyId id
+    /// @return target_id
+    pub fn get_company_redirect(&self, id: CompanyId) -> Option<CompanyId> {
+        self.company_redirects.get(&id)
+    }
+
+    /// Merge duplicate company into target, leaving a redirect on the source
+    /// @param CompanyId source_id
+    /// @param CompanyId target_id
+    /// @return company
+    #[payable]
+    pub fn merge_companies(&mut self, source_id: CompanyId, target_id: CompanyId) -> CompanyView {
+        let initial_storage = env::storage_usage();
+        self.assert_role(Role::Admin);
+        assert!(source_id != target_id, "Can't merge company into itself");
+        self.assert_not_merged(source_id);
+        self.assert_not_merged(target_id);
+        assert!(
+            self.company(source_id).is_some() && self.company(target_id).is_some(),
+            "Company does not exist"
+        );
+        assert!(
+            self.company(target_id).unwrap().activate,
+            "Can't merge into an inactive company"
+        );
+
+        let feedback_count = self
+            .feedbacks_by_company
+            .get(&source_id)
+            .map_or(0, |ids| ids.len() as usize);
+        self.move_merged_feedbacks(source_id, target_id, MAX_MERGE_FEEDBACKS);
+
+        let mut source = self.company(source_id).unwrap();
+        self.set_company_activate(&mut source, false);
+        self.company_redirects.insert(&source_id, &target_id);
+        self.company_ids_by_name
+            .insert(&normalize_company_name(&source.name), &target_id);
+
+        let caller = env::predecessor_account_id();
+        self.log_moderation(
+            &caller,
+            ModerationTarget::Company(source_id),
+            ModerationAction::Merge,
+            Some(format!("Merged into company {}", target_id)),
+        );
+        self.charge_storage(&caller, initial_storage);
+        Event::CompaniesMerged(MergeData {
+            account_id: &caller,
+            source_id,
+            target_id,
+            feedback_count,
+        })
+        .emit();
+
+        self.company_view(self.company(target_id).unwrap())
+    }
+
+    /// Move feedbacks a merge left on the source to the company it redirects to
+    /// @param CompanyId source_id
+    /// @param usize limit
+    /// @return done whether no feedback is left on the source
+    #[payable]
+    pub fn merge_batch(&mut self, source_id: CompanyId, limit: usize) -> bool {
+        let initial_storage = env::storage_usage();
+        self.assert_role(Role::Admin);
+        assert!(
+            self.company_redirects.get(&source_id).is_some(),
+            "Company is not merged"
+        );
+        // The target may have been merged on since
+        let target_id = self.resolve_company_id(source_id);
+        let done = self.move_merged_feedbacks(source_id, target_id, limit);
+
+        self.charge_storage(&env::predecessor_account_id(), initial_storage);
+
+        done
+    }
+}
+
+/// Company name and redirect helpers.
+impl Contract {
+    /// Move up to limit of the newest feedbacks left on source to target,
+    /// recounting ratings and listings, returning whether none is left
+    fn move_merged_feedbacks(
+        &mut self,
+        source_id: CompanyId,
+        target_id: CompanyId,
+        limit: usize,
+    ) -> bool {
+        let index_storage = env::storage_usage();
+        let mut ids = match self.feedbacks_by_company.get(&source_id) {
+            Some(ids) => ids,
+            None => return true,
+        };
+        let mut source_ids = vec![];
+        while source_ids.len() < limit {
+            match ids.pop() {
+                Some(id) => source_ids.push(id),
+                None => break,
+            }
+        }
+        source_ids.reverse();
+        let done = ids.is_empty();
+        if done {
+            self.feedbacks_by_company.remove(&source_id);
+        } else {
+            self.feedbacks_by_company.insert(&source_id, &ids);
+        }
+        self.fund_by_contract(index_storage);
+
+        for id in source_ids.iter() {
+            let old_feedback = self.feedback(*id).unwrap();
+            let mut feedback = old_feedback.clone();
+            feedback.company_id = target_id;
+            self.save_feedback(&feedback);
+            self.sync_rating(Some(&old_feedback), &feedback);
+            if feedback.activate {
+                self.count_feedback(&old_feedback, false);
+                self.count_feedback(&feedback, true);
+            }
+            // Same size, so the manager that paid for it keeps paying
+            if let Some(mut response) = self.official_responses.get(id) {
+                response.company_id = target_id;
+                self.official_responses.insert(id, &response);
+            }
+        }
+
+        let index_storage = env::storage_usage();
+        if done {
+            self.feedback_counts
+                .remove(&FeedbackListing::Company(source_id));
+            self.feedback_counts
+                .remove(&FeedbackListing::CompanyRoot(source_id));
+            self.rating_histograms.remove(&source_id);
+        }
+        let mut target_ids = self
+            .feedbacks_by_company
+            .get(&target_id)
+            .unwrap_or_else(|| {
+                Vector::new(StorageKey::CompanyFeedbacks {
+                    company_id: target_id,
+                })
+            });
+        merge_feedback_ids(&mut target_ids, &source_ids);
+        self.feedbacks_by_company.insert(&target_id, &target_ids);
+        self.fund_by_contract(index_storage);
+
+        done
+    }
+
+    /// Follow redirects of merged companies to the live company id
+    pub(crate) fn resolve_company_id(&self, id: CompanyId) -> CompanyId {
+        let mut id = id;
+        while let Some(target_id) = self.company_redirects.get(&id) {
+            id = target_id;
+        }
+        id
+    }
+
+    /// Merged companies only redirect, they can't be changed
+    pub(crate) fn assert_not_merged(&self, id: CompanyId) {
+        if let Some(target_id) = self.company_redirects.get(&id) {
+            panic!("Company is merged into company {}", target_id);
+        }
+    }
+
+    /// Index company name, panicking when another company has it
+    pub(crate) fn index_company_name(&mut self, company: &Company) {
+        let name = normalize_company_name(&company.name);
+        assert!(!name.is_empty(), "Company name is empty");
+        match self.company_ids_by_name.get(&name) {
+            Some(id) if id != company.id => panic!("Company with this name already exists"),
+            Some(_) => {}
+            None => {
+                self.company_ids_by_name.insert(&name, &company.id);
+            }
+        }
+    }
+
+    /// Move company to its new name in the index
+    pub(crate) fn reindex_company_name(&mut self, old_name: &str, company: &Company) {
+        let old_name = normalize_company_name(old_name);
+        if old_name == normalize_company_name(&company.name) {
+            return;
+        }
+        self.index_company_name(company);
+        if self.company_ids_by_name.get(&old_name) == Some(company.id) {
+            self.company_ids_by_name.remove(&old_name);
+        }
+    }
+
+    /// Index name of an existing company unless taken, so the first company
+    /// indexed keeps a duplicate name
+    pub(crate) fn index_existing_company_name(&mut self, company: &Company) {
+        let name = normalize_company_name(&company.name);
+        if !name.is_empty() && self.company_ids_by_name.get(&name).is_none() {
+            self.company_ids_by_name.insert(&name, &company.id);
+        }
+    }
+}
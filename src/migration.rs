@@ -0,0 +1,413 @@
+/*!
+Versioned contract state and migration between layouts.
+NOTES:
+  - The state layout version is kept under its own storage key, since the
+    layout has to be known before the state can be read. Missing means 0.
+  - Version 0 is the unversioned layout deployed before feedback, user and
+    company rows were stored as versioned enums. Migrating from it:
+    - Feedback::reaction used to be a bare usize (0 = like, 1 = dislike, 2 = ban).
+    - Feedback::parent_id used to be a bare FeedbackId where 0 marked a top-level
+      review, so replies to feedback 0 cannot be told apart and become top-level.
+      So do replies to a parent with a higher id, which didn't exist when the
+      reply was written.
+    - Feedback::rating was unbounded and counted for replies and deactivated
+      reviews too, so company aggregates are recounted. Reviews rated outside
+      the default scale are kept unrated with rating 0, which no aggregate
+      counts, until their author rates them.
+    - User::address is renamed to account_id and the account index is built
+      from it, the lowest user id keeps an account bound to several users.
+    - Companies had no managers, they are left without any until a claim
+      is approved.
+    - Company, user and parent feedback indexes and active counts are built
+      from existing rows.
+    - Storage written before staking is not charged to any account.
+    - migrate only moves the state to the current layout, version 0 rows
+      stay under their own prefixes. The owner calls migrate_batch until it
+      returns true, each call converting a bounded number of ids: users,
+      then companies, then feedbacks, each in id order. Until then rows
+      can't be written and listings show the rows converted so far.
+  - Version 1 is the current layout. Only layouts that were deployed get a
+    version: changing a Contract field after a release needs a new version
+    here with a snapshot of the released layout converting to the current
+    one; changing an entity only needs a new variant of its versioned enum.
+  - upgrade deploys new code and calls migrate in the same batch, so the
+    layout is converted by the new code before any other call can read it.
+*/
+use near_sdk::{Gas, Promise};
+
+use crate::*;
+
+/// Gas kept by upgrade itself, the rest is attached to migrate
+pub const GAS_FOR_UPGRADE: Gas = 20_000_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct FeedbackV0 {
+    pub id: FeedbackId,
+    pub parent_id: FeedbackId,
+    pub user_id: UserId,
+    pub company_id: CompanyId,
+    pub content: String,
+    pub reaction: usize,
+    pub rating: usize,
+    pub up_vote: usize,
+    pub down_vote: usize,
+    pub report_vote: usize,
+    pub create_at: u64,
+    pub update_at: u64,
+    pub activate: bool,
+}
+impl FeedbackV0 {
+    // Convert to current layout with the resolved parent
+    fn into_feedback(self, parent_id: Option<FeedbackId>, scale: &RatingScale) -> Feedback {
+        // Only replies carried a reaction on their parent
+        let reaction = match self.reaction {
+            _ if parent_id.is_none() => Reaction::None,
+            0 => Reaction::Like,
+            1 => Reaction::Dislike,
+            2 => Reaction::Report,
+            _ => Reaction::None,
+        };
+        Feedback {
+            id: self.id,
+            parent_id,
+            user_id: self.user_id,
+            company_id: self.company_id,
+            content: self.content,
+            reaction,
+            rating: match parent_id {
+                None if scale.contains(self.rating) => self.rating,
+                _ => 0,
+            },
+            up_vote: 0,
+            down_vote: 0,
+            report_vote: 0,
+            create_at: self.create_at,
+            update_at: self.update_at,
+            activate: self.activate,
+        }
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct UserV0 {
+    pub id: UserId,
+    pub address: String,
+    pub name: String,
+    pub title: String,
+    pub create_at: u64,
+    pub update_at: u64,
+    pub activate: bool,
+}
+impl UserV0 {
+    // Convert to current layout
+    fn into_user(self) -> User {
+        User {
+            id: self.id,
+            account_id: self.address,
+            name: self.name,
+            title: self.title,
+            create_at: self.create_at,
+            update_at: self.update_at,
+            activate: self.activate,
+        }
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct CompanyV0 {
+    pub id: CompanyId,
+    pub name: String,
+    pub rating: usize,
+    pub rating_count: usize,
+    pub company_type: String,
+    pub size: String,
+    pub address: String,
+    pub create_at: u64,
+    pub update_at: u64,
+    pub activate: bool,
+}
+impl CompanyV0 {
+    // Convert to current layout with ratings left to be recounted
+    fn into_company(self) -> Company {
+        Company {
+            id: self.id,
+            name: self.name,
+            rating: 0,
+            rating_count: 0,
+            company_type: self.company_type,
+            size: self.size,
+            address: self.address,
+            managers: vec![],
+            create_at: self.create_at,
+            update_at: self.update_at,
+            activate: self.activate,
+        }
+    }
+}
+
+/// Version of the state layout written by this code
+pub const STATE_VERSION: u8 = 1;
+
+const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+
+/// Record the state layout written by this code
+pub(crate) fn write_state_version() {
+    env::storage_write(STATE_VERSION_KEY, &[STATE_VERSION]);
+}
+
+/// Rows of a migration stage, converted in id order
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, PartialEq)]
+pub enum MigrationStage {
+    Users,
+    Companies,
+    Feedbacks,
+}
+
+/// Version 0 rows left to convert to the current layout
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Migration {
+    pub users: UnorderedMap<UserId, UserV0>,
+    pub companies: UnorderedMap<CompanyId, CompanyV0>,
+    pub feedbacks: UnorderedMap<FeedbackId, FeedbackV0>,
+    pub stage: MigrationStage,
+
+    /// Next id of the stage to convert
+    pub next_id: usize,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV0 {
+    pub owner_id: String,
+    pub feedbacks: UnorderedMap<FeedbackId, FeedbackV0>,
+    pub users: UnorderedMap<UserId, UserV0>,
+    pub companies: UnorderedMap<CompanyId, CompanyV0>,
+    pub next_feedback_id: FeedbackId,
+    pub next_user_id: UserId,
+    pub next_company_id: CompanyId,
+}
+impl ContractV0 {
+    // Convert to current layout, leaving rows to convert to migrate_batch
+    fn into_contract(self) -> Contract {
+        Contract {
+            owner_id: Some(self.owner_id),
+            pending_owner_id: None,
+            roles: LookupMap::new(StorageKey::Roles),
+            feedbacks: UnorderedMap::new(StorageKey::Feedbacks),
+            users: UnorderedMap::new(StorageKey::Users),
+            user_ids_by_account: LookupMap::new(StorageKey::UserIdsByAccount),
+            votes: LookupMap::new(StorageKey::Votes),
+            feedbacks_by_company: LookupMap::new(StorageKey::FeedbacksByCompany),
+            feedbacks_by_user: LookupMap::new(StorageKey::FeedbacksByUser),
+            feedbacks_by_parent: LookupMap::new(StorageKey::FeedbacksByParent),
+            feedback_counts: LookupMap::new(StorageKey::FeedbackCounts),
+            storage_accounts: LookupMap::new(StorageKey::StorageAccounts),
+            record_payments: LookupMap::new(StorageKey::RecordPayments),
+            accounted_storage: 0,
+            companies: UnorderedMap::new(StorageKey::Companies),
+            active_company_count: 0,
+            rating_scale: RatingScale::default(),
+            rating_histograms: LookupMap::new(StorageKey::RatingHistograms),
+            report_threshold: moderation::DEFAULT_REPORT_THRESHOLD,
+            moderation_queue: IdQueue::new(
+                StorageKey::ModerationQueue,
+                StorageKey::ModerationQueueOrder,
+            ),
+            report_resolutions: LookupMap::new(StorageKey::ReportResolutions),
+            moderation_log: Vector::new(StorageKey::ModerationLog),
+            moderation_log_by_target: LookupMap::new(StorageKey::ModerationLogByTarget),
+            appeals: LookupMap::new(StorageKey::Appeals),
+            open_appeals: IdQueue::new(StorageKey::OpenAppeals, StorageKey::OpenAppealsOrder),
+            claims: LookupMap::new(StorageKey::Claims),
+            claim_ids: LookupMap::new(StorageKey::ClaimIds),
+            pending_claims: IdQueue::new(StorageKey::PendingClaims, StorageKey::PendingClaimsOrder),
+            official_responses: LookupMap::new(StorageKey::OfficialResponses),
+            company_ids_by_name: LookupMap::new(StorageKey::CompanyIdsByName),
+            company_redirects: LookupMap::new(StorageKey::CompanyRedirects),
+            migration: Some(Migration {
+                users: self.users,
+                companies: self.companies,
+                feedbacks: self.feedbacks,
+                stage: MigrationStage::Users,
+                next_id: 0,
+            }),
+            next_feedback_id: self.next_feedback_id,
+            next_user_id: self.next_user_id,
+            next_company_id: self.next_company_id,
+            next_claim_id: 0,
+        }
+    }
+}
+
+/// Contract state in any known layout version.
+pub enum VersionedContract {
+    V0(Box<ContractV0>),
+    V1(Box<Contract>),
+}
+impl VersionedContract {
+    // Read state in the layout recorded for it
+    pub fn read() -> Self {
+        let version = env::storage_read(STATE_VERSION_KEY).map_or(0, |version| version[0]);
+        match version {
+            0 => VersionedContract::V0(Box::new(
+                env::state_read().expect("Old state doesn't exist"),
+            )),
+            1 => VersionedContract::V1(Box::new(
+                env::state_read().expect("Old state doesn't exist"),
+            )),
+            _ => panic!("Unknown state version {}", version),
+        }
+    }
+
+    // Return owner of state in any layout
+    pub fn owner_id(&self) -> Option<&str> {
+        match self {
+            VersionedContract::V0(contract) => Some(&contract.owner_id),
+            VersionedContract::V1(contract) => contract.owner_id.as_deref(),
+        }
+    }
+
+    // Convert to current layout
+    pub fn into_contract(self) -> Contract {
+        match self {
+            VersionedContract::V0(contract) => contract.into_contract(),
+            VersionedContract::V1(contract) => *contract,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Migrate state from any previous layout, a no-op on the current one
+    /// @return contract
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let state = VersionedContract::read();
+        let caller = env::predecessor_account_id();
+        assert!(
+            state.owner_id() == Some(caller.as_str()) || caller == env::current_account_id(),
+            "Only contract owner can call this method."
+        );
+
+        let contract = state.into_contract();
+        write_state_version();
+
+        contract
+    }
+
+    /// Convert up to limit ids of rows left by a migration from version 0
+    /// @param usize limit
+    /// @return done whether no row is left to convert
+    pub fn migrate_batch(&mut self, limit: usize) -> bool {
+        self.only_contract_owner();
+        let mut migration = self
+            .migration
+            .take()
+            .expect("Contract state is already migrated");
+        for _ in 0..limit {
+            if !self.migrate_next(&mut migration) {
+                return true;
+            }
+        }
+        self.migration = Some(migration);
+
+        false
+    }
+
+    /// Deploy new code to the contract account and migrate state with it
+    /// @param Vec<u8> code Wasm as borsh input
+    /// @return promise
+    pub fn upgrade(&mut self, #[serializer(borsh)] code: Vec<u8>) -> Promise {
+        self.only_contract_owner();
+        assert!(!code.is_empty(), "Code is empty");
+        let migrate_gas = env::prepaid_gas()
+            .checked_sub(env::used_gas() + GAS_FOR_UPGRADE)
+            .expect("Not enough gas to upgrade");
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call(b"migrate".to_vec(), vec![], 0, migrate_gas)
+    }
+}
+
+/// Migration helpers.
+impl Contract {
+    /// Rows can't be written until every version 0 row is converted, so no
+    /// call takes an account or a company name of a row left to convert
+    pub(crate) fn assert_migrated(&self) {
+        assert!(
+            self.migration.is_none(),
+            "Contract state is still being migrated"
+        );
+    }
+
+    /// Convert the row with the next id of migration, returning false once
+    /// every stage is done
+    fn migrate_next(&mut self, migration: &mut Migration) -> bool {
+        let id = migration.next_id;
+        match migration.stage {
+            MigrationStage::Users if id < self.next_user_id => {
+                if let Some(user) = migration.users.remove(&id) {
+                    let user = user.into_user();
+                    // Users are converted in id order, the lowest id keeps the account
+                    if self.user_ids_by_account.get(&user.account_id).is_none() {
+                        self.user_ids_by_account.insert(&user.account_id, &user.id);
+                    }
+                    self.save_user(&user);
+                }
+            }
+            MigrationStage::Companies if id < self.next_company_id => {
+                if let Some(company) = migration.companies.remove(&id) {
+                    let company = company.into_company();
+                    if company.activate {
+                        self.active_company_count += 1;
+                    }
+                    self.index_existing_company_name(&company);
+                    self.save_company(&company);
+                }
+            }
+            MigrationStage::Feedbacks if id < self.next_feedback_id => {
+                if let Some(old_feedback) = migration.feedbacks.remove(&id) {
+                    self.migrate_feedback(old_feedback);
+                }
+            }
+            MigrationStage::Users => {
+                migration.stage = MigrationStage::Companies;
+                migration.next_id = 0;
+                return true;
+            }
+            MigrationStage::Companies => {
+                migration.stage = MigrationStage::Feedbacks;
+                migration.next_id = 0;
+                return true;
+            }
+            MigrationStage::Feedbacks => return false,
+        }
+        migration.next_id += 1;
+
+        true
+    }
+
+    /// Convert feedback after its parent, recounting the vote it used to add
+    /// and the company rating
+    fn migrate_feedback(&mut self, old_feedback: FeedbackV0) {
+        // A parent was created before its replies, so it has a lower id and
+        // is converted already
+        let mut parent = match old_feedback.parent_id {
+            0 => None,
+            parent_id if parent_id < old_feedback.id => self.feedback(parent_id),
+            _ => None,
+        };
+        let feedback =
+            old_feedback.into_feedback(parent.as_ref().map(|parent| parent.id), &self.rating_scale);
+        if let Some(parent) = parent.as_mut() {
+            parent.add_vote(feedback.reaction);
+            self.save_feedback(parent);
+        }
+
+        self.sync_rating(None, &feedback);
+        self.save_feedback(&feedback);
+        self.index_feedback(&feedback);
+        if feedback.activate {
+            self.count_feedback(&feedback, true);
+        }
+    }
+}
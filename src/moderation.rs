@@ -0,0 +1,349 @@
+/*!
+Moderation queue for reported feedback.
+NOTES:
+  - Feedback reported report_threshold times is hidden and queued for review.
+  - After a report is resolved, only reports received since then count toward
+    hiding the feedback again.
+  - Resolving restores the feedback or keeps it hidden for good, recording the
+    decision, the moderator and the reason. Activating queued feedback
+    directly is recorded as a restore too.
+  - The queue entry is paid by the reporter whose report hid the feedback.
+  - Every activation change, hide and decision is appended to the moderation
+    log, which is never rewritten. Automatic hides are logged with the
+    contract account as actor.
+*/
+use crate::*;
+use events::{ReportData, ResolutionData, ThresholdData};
+
+/// Reports hiding feedback until the threshold is configured
+pub const DEFAULT_REPORT_THRESHOLD: usize = 3;
+
+/// Outcome of reviewing reported feedback.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, BorshDeserialize, BorshSerialize,
+)]
+pub enum ReportDecision {
+    /// Show feedback again
+    Restore,
+    /// Keep feedback hidden
+    Remove,
+}
+
+/// Entity a moderation action applies to.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, BorshDeserialize, BorshSerialize,
+)]
+pub enum ModerationTarget {
+    Feedback(FeedbackId),
+    User(UserId),
+    Company(CompanyId),
+}
+
+/// Moderation action recorded in the log.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, BorshDeserialize, BorshSerialize,
+)]
+pub enum ModerationAction {
+    Activate,
+    Deactivate,
+    /// Feedback hidden for review after reports
+    Hide,
+    Restore,
+    Remove,
+    /// Feedback activated again on appeal
+    Reinstate,
+    /// Appeal closed without activating feedback
+    CloseAppeal,
+    /// Company claim approved, claimant made manager
+    ApproveClaim,
+    RejectClaim,
+    /// Company merged into another company
+    Merge,
+}
+impl ModerationAction {
+    // Return action of an activation change
+    pub fn activation(activate: bool) -> Self {
+        if activate {
+            ModerationAction::Activate
+        } else {
+            ModerationAction::Deactivate
+        }
+    }
+}
+
+/// Moderation log entry, id is its position in the log.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct ModerationEntry {
+    pub id: usize,
+    pub actor_id: AccountId,
+    pub target: ModerationTarget,
+    pub action: ModerationAction,
+    pub reason: Option<String>,
+    pub create_at: u64,
+}
+
+/// Last decision on reported feedback.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct ReportResolution {
+    pub decision: ReportDecision,
+    pub reason: String,
+    pub moderator_id: AccountId,
+    /// Reports received when resolved
+    pub report_vote: usize,
+    pub resolve_at: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Get reports hiding feedback for review
+    /// @return report_threshold
+    pub fn get_report_threshold(&self) -> usize {
+        self.report_threshold
+    }
+
+    /// Set reports hiding feedback for review
+    /// @param usize report_threshold
+    pub fn set_report_threshold(&mut self, report_threshold: usize) {
+        self.assert_role(Role::Admin);
+        assert!(report_threshold > 0, "Report threshold must be positive");
+        self.report_threshold = report_threshold;
+        Event::ReportThresholdUpdated(ThresholdData {
+            account_id: &env::predecessor_account_id(),
+            report_threshold,
+        })
+        .emit();
+    }
+
+    /// Get feedbacks hidden for review paging, longest waiting first
+    /// @param Cursor cursor
+    /// @param usize size
+    /// @return page
+    pub fn get_moderation_queue(&self, cursor: Option<Cursor>, size: usize) -> Page<Feedback> {
+        self.moderation_queue
+            .page(cursor, size, |id| self.feedback(id))
+    }
+
+    /// Get moderation log paging, newest first
+    /// @param ModerationTarget target only entries of this entity
+    /// @param Cursor cursor
+    /// @param usize size
+    /// @return page
+    pub fn get_moderation_log(
+        &self,
+        target: Option<ModerationTarget>,
+        cursor: Option<Cursor>,
+        size: usize,
+    ) -> Page<ModerationEntry> {
+        let target_ids = match target {
+            Some(target) => match self.moderation_log_by_target.get(&target) {
+                Some(ids) => Some(ids),
+                None => {
+                    return Page {
+                        items: vec![],
+                        total: 0,
+                        truncated: false,
+                        next_cursor: None,
+                    }
+                }
+            },
+            None => None,
+        };
+        // Entry id at a position of the listing, both ascending
+        let id_at = |position: u64| match &target_ids {
+            Some(ids) => ids.get(position).unwrap(),
+            None => position as usize,
+        };
+        let total = target_ids
+            .as_ref()
+            .map_or(self.moderation_log.len(), |ids| ids.len());
+
+        // Position of the cursor, the first entry not before it
+        let end = match cursor {
+            Some(cursor) => {
+                let (mut low, mut high) = (0, total);
+                while low < high {
+                    let middle = (low + high) / 2;
+                    if id_at(middle) < cursor.id {
+                        low = middle + 1;
+                    } else {
+                        high = middle;
+                    }
+                }
+                low
+            }
+            None => total,
+        };
+        let mut entries: Vec<ModerationEntry> = (0..end)
+            .rev()
+            .take(size + 1)
+            .filter_map(|position| self.moderation_log.get(id_at(position) as u64))
+            .collect();
+        let next_cursor = next_cursor(&mut entries, size, |entry| Cursor {
+            key: (entry.create_at as i128).into(),
+            id: entry.id,
+        });
+        Page {
+            items: entries,
+            total: total as usize,
+            truncated: false,
+            next_cursor,
+        }
+    }
+
+    /// Get last decision on reported feedback
+    /// @param FeedbackId feedback_id
+    /// @return resolution
+    pub fn get_report_resolution(&self, feedback_id: FeedbackId) -> Option<ReportResolution> {
+        self.report_resolutions.get(&feedback_id)
+    }
+
+    /// Resolve feedback hidden for review
+    /// @param FeedbackId feedback_id
+    /// @param ReportDecision decision
+    /// @param String reason
+    /// @return feedback
+    #[payable]
+    pub fn resolve_report(
+        &mut self,
+        feedback_id: FeedbackId,
+        decision: ReportDecision,
+        reason: String,
+    ) -> Feedback {
+        let initial_storage = env::storage_usage();
+        self.assert_role(Role::Moderator);
+        let moderator_id = env::predecessor_account_id();
+        assert!(
+            self.moderation_queue.contains(feedback_id),
+            "Feedback is not pending review"
+        );
+        let mut feedback = match self.feedback(feedback_id) {
+            Some(feedback) => feedback,
+            None => panic!("Feedback does not exist"),
+        };
+
+        let resolution = self.record_resolution(&feedback, decision, reason, moderator_id);
+        self.set_feedback_activate(&mut feedback, decision == ReportDecision::Restore);
+        let action = match decision {
+            ReportDecision::Restore => ModerationAction::Restore,
+            ReportDecision::Remove => ModerationAction::Remove,
+        };
+        self.log_moderation(
+            &resolution.moderator_id,
+            ModerationTarget::Feedback(feedback_id),
+            action,
+            Some(resolution.reason.clone()),
+        );
+
+        self.charge_storage(&resolution.moderator_id, initial_storage);
+
+        feedback
+    }
+}
+
+/// Moderation helpers.
+impl Contract {
+    /// Take queued feedback off the queue and record the decision on it, so
+    /// only reports received from now on count toward hiding it again
+    pub(crate) fn record_resolution(
+        &mut self,
+        feedback: &Feedback,
+        decision: ReportDecision,
+        reason: String,
+        moderator_id: AccountId,
+    ) -> ReportResolution {
+        let feedback_id = feedback.id;
+        self.write_record(
+            StorageRecord::QueuedReport(feedback_id),
+            &moderator_id,
+            |contract| {
+                contract.moderation_queue.remove(feedback_id);
+            },
+        );
+        let resolution = ReportResolution {
+            decision,
+            reason,
+            moderator_id,
+            report_vote: feedback.report_vote,
+            resolve_at: env::block_timestamp(),
+        };
+        self.write_record(
+            StorageRecord::ReportResolution(feedback_id),
+            &resolution.moderator_id,
+            |contract| {
+                contract
+                    .report_resolutions
+                    .insert(&feedback_id, &resolution);
+            },
+        );
+        Event::ReportResolved(ResolutionData {
+            account_id: &resolution.moderator_id,
+            feedback_id,
+            resolution: &resolution,
+        })
+        .emit();
+
+        resolution
+    }
+    /// Hide feedback for review once reports since the last decision reach
+    /// the threshold
+    pub(crate) fn check_reports(&mut self, feedback: &mut Feedback) {
+        let resolved_reports = self
+            .report_resolutions
+            .get(&feedback.id)
+            .map_or(0, |resolution| resolution.report_vote);
+        if !feedback.activate
+            || self.moderation_queue.contains(feedback.id)
+            || feedback.report_vote < resolved_reports + self.report_threshold
+        {
+            return;
+        }
+
+        self.write_record(
+            StorageRecord::QueuedReport(feedback.id),
+            &env::predecessor_account_id(),
+            |contract| {
+                contract
+                    .moderation_queue
+                    .insert(feedback.id, env::block_timestamp());
+            },
+        );
+        self.set_feedback_activate(feedback, false);
+        self.log_moderation(
+            &env::current_account_id(),
+            ModerationTarget::Feedback(feedback.id),
+            ModerationAction::Hide,
+            Some(format!("Reported {} times", feedback.report_vote)),
+        );
+        Event::FeedbackQueued(ReportData {
+            account_id: &env::predecessor_account_id(),
+            feedback_id: feedback.id,
+            report_vote: feedback.report_vote,
+        })
+        .emit();
+    }
+
+    /// Append entry to the moderation log and its target index
+    pub(crate) fn log_moderation(
+        &mut self,
+        actor_id: &AccountId,
+        target: ModerationTarget,
+        action: ModerationAction,
+        reason: Option<String>,
+    ) {
+        let entry = ModerationEntry {
+            id: self.moderation_log.len() as usize,
+            actor_id: actor_id.clone(),
+            target,
+            action,
+            reason,
+            create_at: env::block_timestamp(),
+        };
+        self.moderation_log.push(&entry);
+        let mut ids = self
+            .moderation_log_by_target
+            .get(&target)
+            .unwrap_or_else(|| Vector::new(StorageKey::TargetModerationLog { target }));
+        ids.push(&entry.id);
+        self.moderation_log_by_target.insert(&target, &ids);
+    }
+}
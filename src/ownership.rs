@@ -0,0 +1,78 @@
+/*!
+Two-step contract ownership transfer.
+NOTES:
+  - The owner proposes a new owner, which becomes owner only once it accepts,
+    so a mistyped account cannot take or lose the contract.
+  - Proposing again replaces the pending owner.
+  - Renouncing leaves the contract without owner for good, owner-only methods
+    including upgrade can no longer be called.
+*/
+use crate::*;
+use events::OwnerData;
+
+#[near_bindgen]
+impl Contract {
+    /// Get contract owner
+    /// @return owner_id
+    pub fn get_owner(&self) -> Option<AccountId> {
+        self.owner_id.clone()
+    }
+
+    /// Get account proposed as next owner
+    /// @return pending_owner_id
+    pub fn get_pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner_id.clone()
+    }
+
+    /// Propose next owner, who has to accept ownership
+    /// @param AccountId new_owner
+    #[payable]
+    pub fn propose_owner(&mut self, new_owner: AccountId) {
+        let initial_storage = env::storage_usage();
+        self.only_contract_owner();
+        assert!(
+            env::is_valid_account_id(new_owner.as_bytes()),
+            "Owner's account ID is invalid."
+        );
+        self.pending_owner_id = Some(new_owner);
+
+        let caller = env::predecessor_account_id();
+        self.charge_storage(&caller, initial_storage);
+        Event::OwnerProposed(OwnerData {
+            account_id: &caller,
+            old_owner_id: self.owner_id.as_deref(),
+            new_owner_id: self.pending_owner_id.as_deref(),
+        })
+        .emit();
+    }
+
+    /// Accept ownership proposed to the caller
+    pub fn accept_ownership(&mut self) {
+        let caller = env::predecessor_account_id();
+        assert!(
+            self.pending_owner_id.as_deref() == Some(caller.as_str()),
+            "Only proposed owner can accept ownership."
+        );
+        let old_owner_id = self.owner_id.replace(caller);
+        self.pending_owner_id = None;
+        Event::OwnershipTransferred(OwnerData {
+            account_id: &env::predecessor_account_id(),
+            old_owner_id: old_owner_id.as_deref(),
+            new_owner_id: self.owner_id.as_deref(),
+        })
+        .emit();
+    }
+
+    /// Give up ownership, leaving the contract without owner
+    pub fn renounce_ownership(&mut self) {
+        self.only_contract_owner();
+        let old_owner_id = self.owner_id.take();
+        self.pending_owner_id = None;
+        Event::OwnershipTransferred(OwnerData {
+            account_id: &env::predecessor_account_id(),
+            old_owner_id: old_owner_id.as_deref(),
+            new_owner_id: None,
+        })
+        .emit();
+    }
+}
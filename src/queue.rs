@@ -0,0 +1,97 @@
+/*!
+Ids waiting for review in insertion order.
+NOTES:
+  - Backs the moderation queue, open appeals and pending claims, which are
+    all listed longest waiting first.
+  - Ids are ordered by insertion time and id in a TreeMap, so a page reads
+    only its own entries whatever the length of the queue.
+  - Insertion time is kept by id too, to find and remove an entry by id.
+*/
+use near_sdk::collections::TreeMap;
+use near_sdk::IntoStorageKey;
+
+use crate::*;
+
+/// Ids in insertion order with their insertion time.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct IdQueue {
+    /// Insertion time by id
+    added_at: LookupMap<usize, u64>,
+
+    /// Ids by insertion time and id
+    order: TreeMap<(u64, usize), ()>,
+}
+
+impl IdQueue {
+    pub fn new<S: IntoStorageKey>(added_at_prefix: S, order_prefix: S) -> Self {
+        Self {
+            added_at: LookupMap::new(added_at_prefix),
+            order: TreeMap::new(order_prefix),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.len() == 0
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.added_at.contains_key(&id)
+    }
+
+    /// Append id at time added_at, unless queued already
+    pub fn insert(&mut self, id: usize, added_at: u64) {
+        if !self.contains(id) {
+            self.added_at.insert(&id, &added_at);
+            self.order.insert(&(added_at, id), &());
+        }
+    }
+
+    /// Remove id, returning whether it was queued
+    pub fn remove(&mut self, id: usize) -> bool {
+        match self.added_at.remove(&id) {
+            Some(added_at) => {
+                self.order.remove(&(added_at, id));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Page items of queued ids, longest waiting first. Ids item returns
+    /// none for are left out of the page.
+    pub fn page<T, F: Fn(usize) -> Option<T>>(
+        &self,
+        cursor: Option<Cursor>,
+        size: usize,
+        item: F,
+    ) -> Page<T> {
+        let mut ids: Vec<(u64, usize)> = match cursor {
+            Some(cursor) => self
+                .order
+                .iter_from((cursor.key.0 as u64, cursor.id))
+                .map(|(key, _)| key)
+                .take(size + 1)
+                .collect(),
+            None => self
+                .order
+                .iter()
+                .map(|(key, _)| key)
+                .take(size + 1)
+                .collect(),
+        };
+        let next_cursor = next_cursor(&mut ids, size, |(added_at, id)| Cursor {
+            key: (*added_at as i128).into(),
+            id: *id,
+        });
+        Page {
+            items: ids.into_iter().filter_map(|(_, id)| item(id)).collect(),
+            total: self.len(),
+            truncated: false,
+            next_cursor,
+        }
+    }
+}
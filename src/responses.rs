@@ -0,0 +1,140 @@
+/*!
+Official company responses to reviews.
+NOTES:
+  - Company managers post one official response per top-level review of the
+    company, pinned apart from the reply thread.
+  - Any manager of the company edits the response, the last editor is kept
+    as its account.
+  - Feedback pages return each review with its official response.
+*/
+use crate::*;
+use events::ResponseData;
+
+/// Response of the company to one of its top-level reviews.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct OfficialResponse {
+    pub feedback_id: FeedbackId,
+    pub company_id: CompanyId,
+    /// Manager that last wrote the response
+    pub account_id: AccountId,
+    pub content: String,
+    pub create_at: u64,
+    pub update_at: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Get official response to review
+    /// @param FeedbackId feedback_id
+    /// @return response
+    pub fn get_official_response(&self, feedback_id: FeedbackId) -> Option<OfficialResponse> {
+        self.official_responses.get(&feedback_id)
+    }
+
+    /// Post official response of the company to review
+    /// @param FeedbackId feedback_id
+    /// @param String content
+    /// @return response
+    #[payable]
+    pub fn post_official_response(
+        &mut self,
+        feedback_id: FeedbackId,
+        content: String,
+    ) -> OfficialResponse {
+        let initial_storage = env::storage_usage();
+        let feedback = match self.feedback(feedback_id) {
+            Some(feedback) => feedback,
+            None => panic!("Feedback does not exist"),
+        };
+        assert!(
+            feedback.parent_id.is_none(),
+            "Only top-level reviews get official responses"
+        );
+        self.assert_responder(feedback.company_id);
+        assert!(
+            self.official_responses.get(&feedback_id).is_none(),
+            "Official response already exists"
+        );
+        assert!(!content.is_empty(), "Response content is empty");
+
+        let response = OfficialResponse {
+            feedback_id,
+            company_id: feedback.company_id,
+            account_id: env::predecessor_account_id(),
+            content,
+            create_at: env::block_timestamp(),
+            update_at: env::block_timestamp(),
+        };
+        self.write_record(
+            StorageRecord::OfficialResponse(feedback_id),
+            &response.account_id,
+            |contract| {
+                contract.official_responses.insert(&feedback_id, &response);
+            },
+        );
+
+        self.charge_storage(&response.account_id, initial_storage);
+        Event::OfficialResponsePosted(ResponseData {
+            account_id: &response.account_id,
+            response: &response,
+        })
+        .emit();
+
+        response
+    }
+
+    /// Update official response of the company to review
+    /// @param FeedbackId feedback_id
+    /// @param String content
+    /// @return response
+    #[payable]
+    pub fn update_official_response(
+        &mut self,
+        feedback_id: FeedbackId,
+        content: String,
+    ) -> OfficialResponse {
+        let initial_storage = env::storage_usage();
+        let mut response = match self.official_responses.get(&feedback_id) {
+            Some(response) => response,
+            None => panic!("Official response does not exist"),
+        };
+        self.assert_responder(response.company_id);
+        assert!(!content.is_empty(), "Response content is empty");
+
+        response.account_id = env::predecessor_account_id();
+        response.content = content;
+        response.update_at = env::block_timestamp();
+        self.write_record(
+            StorageRecord::OfficialResponse(feedback_id),
+            &response.account_id,
+            |contract| {
+                contract.official_responses.insert(&feedback_id, &response);
+            },
+        );
+
+        self.charge_storage(&response.account_id, initial_storage);
+        Event::OfficialResponseUpdated(ResponseData {
+            account_id: &response.account_id,
+            response: &response,
+        })
+        .emit();
+
+        response
+    }
+}
+
+/// Official response helpers.
+impl Contract {
+    /// Only managers of an active company may respond for it
+    fn assert_responder(&self, company_id: CompanyId) {
+        let company = match self.company(company_id) {
+            Some(company) => company,
+            None => panic!("Company does not exist"),
+        };
+        assert!(company.activate, "Company is not active");
+        assert!(
+            company.managers.contains(&env::predecessor_account_id()),
+            "Only company managers can respond for this company."
+        );
+    }
+}
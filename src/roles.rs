@@ -0,0 +1,139 @@
+/*!
+Role-based access control.
+NOTES:
+  - Owner is the contract owner account, it can't be granted and moves only
+    through the ownership transfer.
+  - Owner holds every role. Admin holds every role but Owner.
+  - Only the owner grants and revokes Admin, admins grant and revoke
+    Moderator and CompanyVerifier.
+  - Moderators activate feedbacks and users, company verifiers activate
+    companies.
+*/
+use crate::*;
+use events::RoleData;
+
+/// Privilege held by an account.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, BorshDeserialize, BorshSerialize,
+)]
+pub enum Role {
+    /// Contract owner
+    Owner,
+    /// Manages moderators and company verifiers
+    Admin,
+    /// Activates and deactivates feedbacks and users
+    Moderator,
+    /// Activates and deactivates companies
+    CompanyVerifier,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Check account holds role, directly or through a higher one
+    /// @param AccountId account_id
+    /// @param Role role
+    /// @return has_role
+    pub fn has_role(&self, account_id: AccountId, role: Role) -> bool {
+        if self.is_contract_owner(&account_id) {
+            return true;
+        }
+        let roles = self.roles.get(&account_id).unwrap_or_default();
+        match role {
+            Role::Owner => false,
+            Role::Admin => roles.contains(&Role::Admin),
+            _ => roles.contains(&Role::Admin) || roles.contains(&role),
+        }
+    }
+
+    /// Get roles granted to account, without Owner
+    /// @param AccountId account_id
+    /// @return roles
+    pub fn get_roles(&self, account_id: AccountId) -> Vec<Role> {
+        self.roles.get(&account_id).unwrap_or_default()
+    }
+
+    /// Grant role to account
+    /// @param AccountId account_id
+    /// @param Role role
+    #[payable]
+    pub fn grant_role(&mut self, account_id: AccountId, role: Role) {
+        let initial_storage = env::storage_usage();
+        self.assert_role_manager(role);
+        assert!(
+            env::is_valid_account_id(account_id.as_bytes()),
+            "Account ID is invalid."
+        );
+        let mut roles = self.roles.get(&account_id).unwrap_or_default();
+        let caller = env::predecessor_account_id();
+        if !roles.contains(&role) {
+            roles.push(role);
+            self.write_record(
+                StorageRecord::Roles(account_id.clone()),
+                &caller,
+                |contract| {
+                    contract.roles.insert(&account_id, &roles);
+                },
+            );
+        }
+
+        self.charge_storage(&caller, initial_storage);
+        Event::RoleGranted(RoleData {
+            account_id: &caller,
+            holder_id: &account_id,
+            role,
+        })
+        .emit();
+    }
+
+    /// Revoke role from account
+    /// @param AccountId account_id
+    /// @param Role role
+    #[payable]
+    pub fn revoke_role(&mut self, account_id: AccountId, role: Role) {
+        let initial_storage = env::storage_usage();
+        self.assert_role_manager(role);
+        let mut roles = self.roles.get(&account_id).unwrap_or_default();
+        roles.retain(|granted| *granted != role);
+        let caller = env::predecessor_account_id();
+        self.write_record(
+            StorageRecord::Roles(account_id.clone()),
+            &caller,
+            |contract| {
+                if roles.is_empty() {
+                    contract.roles.remove(&account_id);
+                } else {
+                    contract.roles.insert(&account_id, &roles);
+                }
+            },
+        );
+
+        self.charge_storage(&caller, initial_storage);
+        Event::RoleRevoked(RoleData {
+            account_id: &caller,
+            holder_id: &account_id,
+            role,
+        })
+        .emit();
+    }
+}
+
+/// Role helpers.
+impl Contract {
+    /// Only accounts holding role may call the method
+    pub(crate) fn assert_role(&self, role: Role) {
+        assert!(
+            self.has_role(env::predecessor_account_id(), role),
+            "Only {:?} can call this method.",
+            role
+        );
+    }
+
+    /// Only the owner may manage admins, only admins may manage other roles
+    fn assert_role_manager(&self, role: Role) {
+        match role {
+            Role::Owner => panic!("Owner role moves only through ownership transfer."),
+            Role::Admin => self.assert_role(Role::Owner),
+            Role::Moderator | Role::CompanyVerifier => self.assert_role(Role::Admin),
+        }
+    }
+}
@@ -0,0 +1,344 @@
+/*!
+NEP-145 storage management.
+NOTES:
+  - Every account that writes state keeps a storage deposit with the contract.
+  - Writes charge the bytes they add to the caller and refund the bytes they
+    free, so the contract account pays for no row of user data.
+  - Records another account may rewrite or free are paid by one account:
+    - Feedback and user rows are always paid by the account of their author,
+      whoever edits them.
+    - A StorageRecord is paid in full by the last account that wrote it. The
+      payer and the bytes it paid are kept in record_payments, so the
+      previous payer gets back exactly what it paid. Roles, moderation
+      queue entries and decisions, appeals, claims, official responses and
+      company rows are StorageRecords, so a moderator, verifier or manager
+      deciding or editing one pays for it from then on.
+  - Feedback indexes, listing counts and rating histograms are paid by the
+    contract account. They grow with every feedback whoever wrote it and
+    shrink when companies merge, so no account could be refunded for them.
+  - Deposit attached to a payable write is credited before it is charged.
+  - Accounts are registered by their first write, which needs at least the
+    minimum balance attached.
+  - The minimum balance covers registering, creating a user and
+    MIN_STORAGE_FEEDBACKS feedback rows, computed from their Borsh sizes with
+    the longest account id and MIN_STORAGE_TEXT_LEN bytes of each text.
+  - Force unregistration is not supported, accounts with a user or other
+    stored data panic.
+*/
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds, StorageManagement,
+};
+use near_sdk::json_types::{ValidAccountId, U128};
+use near_sdk::{assert_one_yocto, log, Balance, Promise, StorageUsage};
+
+use crate::*;
+
+/// Feedback entries covered by the minimum storage balance
+pub const MIN_STORAGE_FEEDBACKS: StorageUsage = 10;
+
+/// Bytes of each user name, title and feedback content covered by the
+/// minimum storage balance
+pub const MIN_STORAGE_TEXT_LEN: StorageUsage = 100;
+
+// Bytes stored with every entry on top of its key and value
+const ENTRY_BYTES: StorageUsage = 40;
+// Borsh sizes of ids, the longest account id and a covered text
+const ID_BYTES: StorageUsage = 8;
+const ACCOUNT_ID_BYTES: StorageUsage = 4 + 64;
+const TEXT_BYTES: StorageUsage = 4 + MIN_STORAGE_TEXT_LEN;
+// Prefix of a collection keyed by StorageKey unit variant
+const PREFIX_BYTES: StorageUsage = 1;
+
+/// Bytes of an entry of a LookupMap or Vector
+const fn entry_bytes(key: StorageUsage, value: StorageUsage) -> StorageUsage {
+    ENTRY_BYTES + key + value
+}
+
+/// Bytes of an entry of an UnorderedMap with an id key, which keeps the key
+/// index, key and value under their own prefix
+const fn unordered_entry_bytes(value: StorageUsage) -> StorageUsage {
+    let key = PREFIX_BYTES + 1 + ID_BYTES;
+    entry_bytes(key, ID_BYTES) + entry_bytes(key, ID_BYTES) + entry_bytes(key, value)
+}
+
+/// Bytes of the storage account of an account
+const fn storage_account_bytes(account_id_len: StorageUsage) -> StorageUsage {
+    // Deposit and used bytes
+    entry_bytes(PREFIX_BYTES + 4 + account_id_len, 16 + 8)
+}
+
+/// Bytes covered by the minimum storage balance
+pub const fn min_storage_usage() -> StorageUsage {
+    let account_key = PREFIX_BYTES + ACCOUNT_ID_BYTES;
+    // Layout tag, id, account id, name, title, timestamps and activate
+    let user = 1 + ID_BYTES + ACCOUNT_ID_BYTES + 2 * TEXT_BYTES + 2 * 8 + 1;
+    let account = storage_account_bytes(64)
+        + unordered_entry_bytes(user)
+        + entry_bytes(account_key, ID_BYTES);
+
+    // Layout tag, id, parent, user, company, content, reaction, rating,
+    // votes, timestamps and activate
+    let feedback =
+        1 + ID_BYTES + 1 + ID_BYTES + 2 * ID_BYTES + TEXT_BYTES + 1 + 4 * ID_BYTES + 2 * 8 + 1;
+    account + MIN_STORAGE_FEEDBACKS * unordered_entry_bytes(feedback)
+}
+
+/// Record that another account than its payer may rewrite or free.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum StorageRecord {
+    /// Company row and its name index entry
+    Company(CompanyId),
+    Roles(AccountId),
+    /// Moderation queue entry of hidden feedback
+    QueuedReport(FeedbackId),
+    ReportResolution(FeedbackId),
+    /// Appeal and its open appeal entry
+    Appeal(FeedbackId),
+    /// Claim, its pending entry and claimant index entry
+    Claim(ClaimId),
+    OfficialResponse(FeedbackId),
+}
+
+/// Account paying for a record and the bytes charged to it.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct RecordPayment {
+    pub account_id: AccountId,
+    pub bytes: StorageUsage,
+}
+
+/// Storage deposit of an account and the bytes charged against it.
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct StorageAccount {
+    pub deposit: Balance,
+    pub used_bytes: StorageUsage,
+}
+impl StorageAccount {
+    // Return deposit locked by used bytes
+    pub fn locked(&self) -> Balance {
+        self.used_bytes as Balance * env::storage_byte_cost()
+    }
+
+    // Return deposit free to withdraw
+    pub fn available(&self) -> Balance {
+        self.deposit.saturating_sub(self.locked())
+    }
+
+    // Return balance in the standard view
+    pub fn balance(&self) -> StorageBalance {
+        StorageBalance {
+            total: self.deposit.into(),
+            available: self.available().into(),
+        }
+    }
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    /// Deposit storage balance for account, registering it if needed
+    /// @param ValidAccountId account_id defaults to the caller
+    /// @param bool registration_only refunds deposit above the minimum balance
+    /// @return balance
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let initial_storage = env::storage_usage();
+        let amount = env::attached_deposit();
+        let account_id = account_id.map_or_else(env::predecessor_account_id, String::from);
+        let registered = self.storage_accounts.get(&account_id).is_some();
+        let min_balance = self.storage_balance_bounds().min.0;
+        self.charge_storage(&account_id, initial_storage);
+
+        let mut account = self.storage_accounts.get(&account_id).unwrap();
+        if registration_only.unwrap_or(false) {
+            let refund = if registered {
+                amount
+            } else {
+                amount - min_balance
+            };
+            if refund > 0 {
+                account.deposit -= refund;
+                self.storage_accounts.insert(&account_id, &account);
+                Promise::new(env::predecessor_account_id()).transfer(refund);
+            }
+        }
+
+        account.balance()
+    }
+
+    /// Withdraw available storage balance of the caller
+    /// @param U128 amount defaults to all available balance
+    /// @return balance
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut account = match self.storage_accounts.get(&account_id) {
+            Some(account) => account,
+            None => panic!("Account is not registered"),
+        };
+
+        let available = account.available();
+        let amount = amount.map_or(available, |amount| amount.0);
+        assert!(amount <= available, "Not enough available storage balance");
+        if amount > 0 {
+            account.deposit -= amount;
+            self.storage_accounts.insert(&account_id, &account);
+            Promise::new(account_id).transfer(amount);
+        }
+
+        account.balance()
+    }
+
+    /// Unregister the caller and return its deposit, only without stored data
+    /// @param bool force is not supported
+    /// @return unregistered
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        assert!(
+            !force.unwrap_or(false),
+            "Force unregistration is not supported"
+        );
+        let account_id = env::predecessor_account_id();
+        let account = match self.storage_accounts.get(&account_id) {
+            Some(account) => account,
+            None => {
+                log!("The account {} is not registered", &account_id);
+                return false;
+            }
+        };
+
+        assert!(
+            self.user_ids_by_account.get(&account_id).is_none(),
+            "Can't unregister the account with a user"
+        );
+        // Records paid by the account are charged on top of its own entry
+        assert!(
+            account.used_bytes <= storage_account_bytes(account_id.len() as StorageUsage),
+            "Can't unregister the account with stored data"
+        );
+        self.storage_accounts.remove(&account_id);
+        if account.deposit > 0 {
+            Promise::new(account_id).transfer(account.deposit);
+        }
+
+        true
+    }
+
+    /// Get storage balance bounds, with no maximum
+    /// @return bounds
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: (min_storage_usage() as Balance * env::storage_byte_cost()).into(),
+            max: None,
+        }
+    }
+
+    /// Get storage balance of account
+    /// @param ValidAccountId account_id
+    /// @return balance
+    fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance> {
+        self.storage_accounts
+            .get(account_id.as_ref())
+            .map(|account| account.balance())
+    }
+}
+
+/// Storage staking helpers.
+impl Contract {
+    /// Credit attached deposit to account and charge it the storage written
+    /// since initial_storage, refunding storage freed. Bytes already charged
+    /// to other accounts during the call are left out.
+    pub(crate) fn charge_storage(&mut self, account_id: &AccountId, initial_storage: StorageUsage) {
+        let mut account = match self.storage_accounts.get(account_id) {
+            Some(account) => account,
+            None => {
+                assert!(
+                    env::attached_deposit() >= self.storage_balance_bounds().min.0,
+                    "The attached deposit is less than the minimum storage balance"
+                );
+                StorageAccount::default()
+            }
+        };
+        account.deposit += env::attached_deposit();
+        // Write the record first so registration is charged too
+        self.storage_accounts.insert(account_id, &account);
+
+        let written = env::storage_usage() as i64 - initial_storage as i64 - self.accounted_storage;
+        self.accounted_storage = 0;
+        account.used_bytes = (account.used_bytes as i64 + written).max(0) as StorageUsage;
+        assert!(
+            account.deposit >= account.locked(),
+            "Not enough storage deposit"
+        );
+        self.storage_accounts.insert(account_id, &account);
+    }
+
+    /// Charge account_id the storage written since initial_storage to a row
+    /// it pays for whoever writes it
+    pub(crate) fn charge_row(&mut self, account_id: &AccountId, initial_storage: StorageUsage) {
+        let bytes = env::storage_usage() as i64 - initial_storage as i64;
+        self.add_used_bytes(account_id, bytes);
+    }
+
+    /// Leave the storage written since initial_storage to the contract, no
+    /// account is charged or refunded for it
+    pub(crate) fn fund_by_contract(&mut self, initial_storage: StorageUsage) {
+        self.accounted_storage += env::storage_usage() as i64 - initial_storage as i64;
+    }
+
+    /// Write record through write, making account_id pay for all of it and
+    /// refunding the account that paid for it before
+    pub(crate) fn write_record<F: FnOnce(&mut Self)>(
+        &mut self,
+        record: StorageRecord,
+        account_id: &AccountId,
+        write: F,
+    ) {
+        let initial_storage = env::storage_usage();
+        let previous = self.record_payments.remove(&record);
+        write(self);
+
+        // Bytes of the record, with its payment once written
+        let previous_bytes = previous.as_ref().map_or(0, |payment| payment.bytes as i64);
+        let bytes = || previous_bytes + env::storage_usage() as i64 - initial_storage as i64;
+        if bytes() > 0 {
+            let mut payment = RecordPayment {
+                account_id: account_id.clone(),
+                bytes: 0,
+            };
+            self.record_payments.insert(&record, &payment);
+            payment.bytes = bytes() as StorageUsage;
+            self.record_payments.insert(&record, &payment);
+            self.add_used_bytes(account_id, payment.bytes as i64);
+        }
+        if let Some(previous) = previous {
+            self.add_used_bytes(&previous.account_id, -(previous.bytes as i64));
+        }
+    }
+
+    /// Charge or refund bytes to an account. The caller is charged with the
+    /// rest of the call by charge_storage, after its deposit is credited.
+    fn add_used_bytes(&mut self, account_id: &AccountId, bytes: i64) {
+        if *account_id == env::predecessor_account_id() {
+            return;
+        }
+        self.accounted_storage += bytes;
+        match self.storage_accounts.get(account_id) {
+            Some(mut account) => {
+                account.used_bytes = (account.used_bytes as i64 + bytes).max(0) as StorageUsage;
+                assert!(
+                    bytes <= 0 || account.deposit >= account.locked(),
+                    "Not enough storage deposit of {}",
+                    account_id
+                );
+                self.storage_accounts.insert(account_id, &account);
+            }
+            // Rows written before storage staking were never paid for
+            None => assert!(bytes <= 0, "Account {} is not registered", account_id),
+        }
+    }
+}
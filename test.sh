@@ -1,13 +1,31 @@
 source neardev/dev-account.env
 
 cat "==> Call fn get_feedbacks"
-near view $CONTRACT_NAME get_feedbacks '{"page": 0, "size": 10}' --accountId=$CONTRACT_NAME
+near view $CONTRACT_NAME get_feedbacks '{"size": 10}' --accountId=$CONTRACT_NAME
+
+cat "==> Call fn storage_balance_bounds"
+near view $CONTRACT_NAME storage_balance_bounds '{}'
+
+cat "==> Call fn storage_deposit"
+near call $CONTRACT_NAME storage_deposit '{}' --accountId=$CONTRACT_NAME --deposit 0.1
+
+cat "==> Call fn storage_balance_of"
+near view $CONTRACT_NAME storage_balance_of "{\"account_id\": \"$CONTRACT_NAME\"}"
+
+cat "==> Call fn create_user"
+near call $CONTRACT_NAME create_user '{ "user": { "id": 0, "account_id": "", "name": "Mock User", "title": "Mock Title", "create_at": 0, "update_at": 0, "activate": true } }' --accountId=$CONTRACT_NAME
+
+cat "==> Call fn create_company"
+near call $CONTRACT_NAME create_company '{ "company": { "id": 0, "name": "Mock Company", "rating": 0, "rating_count": 0, "company_type": "Mock Company Type", "size": "Mock Company Size", "address": "Mock Company Address", "managers": [], "create_at": 0, "update_at": 0, "activate": true } }' --accountId=$CONTRACT_NAME
+
+cat "==> Call fn update_active_company"
+near call $CONTRACT_NAME update_active_company '{ "id": 0, "activate": true, "reason": "Verified" }' --accountId=$CONTRACT_NAME
 
 cat "==> Call fn create_feedback"
-near call $CONTRACT_NAME create_feedback '{ "feedback": { "id": 0, "parent_id": 0, "user_id": 0, "company_id": 0, "content": "Mock feedback", "reaction": 4, "rating": 0, "up_vote": 0, "down_vote": 0, "report_vote": 0, "create_at": 0, "update_at": 0, "activate": true } }' --accountId=$CONTRACT_NAME
+near call $CONTRACT_NAME create_feedback '{ "feedback": { "id": 0, "parent_id": null, "user_id": 0, "company_id": 0, "content": "Mock feedback", "reaction": "None", "rating": 5, "up_vote": 0, "down_vote": 0, "report_vote": 0, "create_at": 0, "update_at": 0, "activate": true } }' --accountId=$CONTRACT_NAME
 
 cat "==> Call fn update_active_feedback"
-near call $CONTRACT_NAME update_active_feedback '{ "id": 1, "activate": true }' --accountId=$CONTRACT_NAME
+near call $CONTRACT_NAME update_active_feedback '{ "id": 1, "activate": true, "reason": "Reviewed" }' --accountId=$CONTRACT_NAME
 
 cat "==> Call fn get_feedbacks"
-near view $CONTRACT_NAME get_feedbacks '{"page": 0, "size": 10}' --accountId=$CONTRACT_NAME
+near view $CONTRACT_NAME get_feedbacks '{"size": 10}' --accountId=$CONTRACT_NAME
@@ -0,0 +1,8 @@
+export file=target/wasm32-unknown-unknown/release/rust_decentralized_company_feedback.wasm
+
+cargo build --target wasm32-unknown-unknown --release
+
+source neardev/dev-account.env
+# upgrade takes the code as borsh Vec<u8>: little-endian u32 length, then the bytes
+args=$(python3 -c "import base64, struct, sys; code = open(sys.argv[1], 'rb').read(); print(base64.b64encode(struct.pack('<I', len(code)) + code).decode())" $file)
+near call $CONTRACT_NAME upgrade $args --base64 --accountId=${OWNER_ID:-$CONTRACT_NAME} --gas=300000000000000
@@ -6,31 +6,30 @@ const PER_PAGE_LIMIT = 3;
 
 const FeedbackList = ({ contract }) => {
   const [feedbacks, setFeedbacks] = useState([]);
-  const [page, setPage] = useState(1);
+  const [total, setTotal] = useState(0);
+  const [nextCursor, setNextCursor] = useState(null);
+  // cursors of the pages visited so far, the first page has none
+  const [cursors, setCursors] = useState([null]);
+  const page = cursors.length;
+  const cursor = cursors[page - 1];
 
   useEffect(() => {
-    let offset;
-    if (page < 1) {
-      setPage(1);
-      offset = 0;
-    } else {
-      offset = (page - 1) * PER_PAGE_LIMIT;
-    }
-
     // every second after the component first mounts
     // update the list of feedbacks by invoking the get
     // method on the smart contract
     const id = setInterval(() => {
       contract
-        .get_feedbacks({ page: offset, size: PER_PAGE_LIMIT })
-        .then((feedbacks) => {
-          console.log("get_feedbacks::", feedbacks);
-          return setFeedbacks(feedbacks);
+        .get_feedbacks({ cursor, size: PER_PAGE_LIMIT })
+        .then((result) => {
+          console.log("get_feedbacks::", result);
+          setTotal(result.total);
+          setNextCursor(result.next_cursor);
+          return setFeedbacks(result.items);
         });
     }, 1000);
 
     return () => clearInterval(id);
-  }, [page, contract]);
+  }, [cursor, contract]);
 
   return (
     <ul>
@@ -40,9 +39,21 @@ const FeedbackList = ({ contract }) => {
         </li>
       ))}
       <hr />
-      <div className="flex">Current Page: {page}</div>
-      <button onClick={() => setPage((page) => page - 1)}>&lt;</button>{" "}
-      <button onClick={() => setPage((page) => page + 1)}>&gt;</button>
+      <div className="flex">
+        Current Page: {page} ({total} feedbacks)
+      </div>
+      <button
+        disabled={page <= 1}
+        onClick={() => setCursors((cursors) => cursors.slice(0, -1))}
+      >
+        &lt;
+      </button>{" "}
+      <button
+        disabled={!nextCursor}
+        onClick={() => setCursors((cursors) => [...cursors, nextCursor])}
+      >
+        &gt;
+      </button>
     </ul>
   );
 };
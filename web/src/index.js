@@ -47,6 +47,10 @@ async function initContract() {
         "get_feedbacks_by_company_id_paging",
         "get_companies_paging",
         "get_company",
+        "get_official_response",
+        "find_company_by_name",
+        "storage_balance_of",
+        "storage_balance_bounds",
       ],
       // Change methods can modify the state, but you don't receive the returned value when called
       changeMethods: [
@@ -59,6 +63,9 @@ async function initContract() {
         "update_active_feedback",
         "update_active_user",
         "update_active_company",
+        "storage_deposit",
+        "storage_withdraw",
+        "storage_unregister",
       ],
       // Sender is the account ID to initialize transactions.
       // getAccountId() will return empty string if user is still unauthorized